@@ -1,11 +1,16 @@
 use kludgine::prelude::*;
 use once_cell::sync::OnceCell;
 use rodio::{
-    decoder::Decoder,
+    decoder::{Decoder, DecoderError},
     source::{Buffered, Source},
 };
 use std::io::Cursor;
 
+const NOT_LOADED: &str = "assets::load runs before the game starts";
+
+static ANIMATIONS: OnceCell<Vec<Animation>> = OnceCell::new();
+static CLICK: OnceCell<Buffered<Decoder<Cursor<&'static [u8]>>>> = OnceCell::new();
+
 #[derive(Clone, Debug)]
 pub struct Animation {
     pub id: usize,
@@ -13,70 +18,142 @@ pub struct Animation {
 }
 
 impl Animation {
-    pub async fn all() -> &'static Vec<Animation> {
-        static ANIMATIONS: OnceCell<Vec<Animation>> = OnceCell::new();
-        if let Some(animations) = ANIMATIONS.get() {
-            return animations;
-        }
+    pub fn all() -> &'static Vec<Animation> {
+        ANIMATIONS.get().expect(NOT_LOADED)
+    }
 
-        let small_planet = include_aseprite_sprite!("../assets/whitevault/space/SmallPlanet")
-            .await
-            .unwrap();
-        let small_blue_planet =
-            include_aseprite_sprite!("../assets/whitevault/space/SmallPlanet-Blue")
-                .await
-                .unwrap();
-        let ufo = include_aseprite_sprite!("../assets/whitevault/space/ufo")
-            .await
-            .unwrap();
-        let planet2 = include_aseprite_sprite!("../assets/whitevault/space/Planet2")
-            .await
-            .unwrap();
-        let planet3 = include_aseprite_sprite!("../assets/whitevault/space/Planet3")
-            .await
-            .unwrap();
-        let planet4 = include_aseprite_sprite!("../assets/whitevault/space/Planet4")
-            .await
-            .unwrap();
-        let space_case = include_aseprite_sprite!("../assets/whitevault/space/Space_case")
-            .await
-            .unwrap();
-        let astro = include_aseprite_sprite!("../assets/whitevault/space/astro")
-            .await
-            .unwrap();
-        let animations = vec![
-            Animation {
-                sprite: small_planet,
-                id: 0,
-            },
-            Animation {
-                sprite: small_blue_planet,
-                id: 1,
-            },
-            Animation { sprite: ufo, id: 2 },
-            Animation {
-                sprite: planet2,
-                id: 3,
-            },
-            Animation {
-                sprite: planet3,
-                id: 4,
-            },
-            Animation {
-                sprite: planet4,
-                id: 5,
-            },
-            Animation {
-                sprite: space_case,
-                id: 6,
-            },
-            Animation {
-                sprite: astro,
-                id: 7,
-            },
+    async fn load(loader: &mut Loader) -> Vec<Animation> {
+        let sprites = vec![
+            (
+                "SmallPlanet",
+                include_aseprite_sprite!("../assets/whitevault/space/SmallPlanet").await,
+            ),
+            (
+                "SmallPlanet-Blue",
+                include_aseprite_sprite!("../assets/whitevault/space/SmallPlanet-Blue").await,
+            ),
+            (
+                "ufo",
+                include_aseprite_sprite!("../assets/whitevault/space/ufo").await,
+            ),
+            (
+                "Planet2",
+                include_aseprite_sprite!("../assets/whitevault/space/Planet2").await,
+            ),
+            (
+                "Planet3",
+                include_aseprite_sprite!("../assets/whitevault/space/Planet3").await,
+            ),
+            (
+                "Planet4",
+                include_aseprite_sprite!("../assets/whitevault/space/Planet4").await,
+            ),
+            (
+                "Space_case",
+                include_aseprite_sprite!("../assets/whitevault/space/Space_case").await,
+            ),
+            (
+                "astro",
+                include_aseprite_sprite!("../assets/whitevault/space/astro").await,
+            ),
         ];
-        ANIMATIONS.set(animations).unwrap();
-        ANIMATIONS.get().unwrap()
+        sprites
+            .into_iter()
+            .enumerate()
+            .filter_map(|(id, (name, sprite))| {
+                loader
+                    .load(name, sprite)
+                    .map(|sprite| Animation { sprite, id })
+            })
+            .collect()
+    }
+}
+
+/// The click played by the metronome.
+pub fn click() -> &'static Buffered<Decoder<Cursor<&'static [u8]>>> {
+    CLICK.get().expect(NOT_LOADED)
+}
+
+/// Loads and decodes everything in the pack up front, so a broken asset is
+/// reported before the game starts instead of partway through it.
+pub async fn load() -> anyhow::Result<()> {
+    let mut loader = Loader::default();
+    load_into(&mut loader).await;
+    loader.finish()
+}
+
+/// Loads everything in the pack, recording what failed in `loader`.
+pub async fn load_into(loader: &mut Loader) {
+    for audio_loop in Loop::all() {
+        if audio_loop.source.get().is_none() {
+            if let Some(source) = loader.decode(audio_loop.name, audio_loop.audio) {
+                let _ = audio_loop.source.set(source);
+            }
+        }
+    }
+    if CLICK.get().is_none() {
+        if let Some(click) = loader.decode("click", include_bytes!("../assets/ecton/click.ogg")) {
+            let _ = CLICK.set(click);
+        }
+    }
+
+    if ANIMATIONS.get().is_none() {
+        let animations = Animation::load(loader).await;
+        let _ = ANIMATIONS.set(animations);
+    }
+}
+
+/// Loads assets one after another, collecting every one that fails rather
+/// than stopping at the first.
+#[derive(Default)]
+pub struct Loader {
+    /// How many assets have been loaded, successfully or not
+    pub checked: usize,
+    pub errors: Vec<anyhow::Error>,
+}
+
+impl Loader {
+    /// Records the result of loading the asset called `name`.
+    pub fn load<T>(&mut self, name: &str, result: KludgineResult<T>) -> Option<T> {
+        self.checked += 1;
+        match result {
+            Ok(asset) => Some(asset),
+            Err(err) => {
+                self.errors
+                    .push(anyhow::anyhow!("loading {}: {:?}", name, err));
+                None
+            }
+        }
+    }
+
+    /// Decodes the audio called `name`.
+    pub fn decode(
+        &mut self,
+        name: &str,
+        bytes: &'static [u8],
+    ) -> Option<Buffered<Decoder<Cursor<&'static [u8]>>>> {
+        self.checked += 1;
+        match Loop::decode(bytes) {
+            Ok(source) => Some(source.buffered()),
+            Err(err) => {
+                self.errors
+                    .push(anyhow::anyhow!("decoding {}: {:?}", name, err));
+                None
+            }
+        }
+    }
+
+    /// Returns every error as one, if anything failed.
+    pub fn finish(self) -> anyhow::Result<()> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        let messages = self
+            .errors
+            .iter()
+            .map(|err| format!("{:#}", err))
+            .collect::<Vec<_>>();
+        anyhow::bail!("{}", messages.join("\n"))
     }
 }
 
@@ -85,22 +162,129 @@ pub enum LoopKind {
     PADs,
     ARPs,
     Leads,
-    Drums,
+    Drums(DrumPart),
     Bass,
     Piano,
 }
 
+/// The pieces of the drum kit. Each part is its own kind of loop, so the
+/// drums can be built up one element at a time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DrumPart {
+    KickSnare,
+    Hats,
+    Percussion,
+}
+
+/// A single entry in a loop's beat map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    pub beat: f32,
+    pub kind: NoteKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteKind {
+    /// Click on the beat.
+    Tap,
+    /// Press on the beat and release `length` beats later.
+    Hold { length: f32 },
+    /// Press on the beat, drag onto another element and release there
+    /// `length` beats later.
+    Drag { length: f32 },
+}
+
+impl Note {
+    pub const fn tap(beat: f32) -> Self {
+        Self {
+            beat,
+            kind: NoteKind::Tap,
+        }
+    }
+
+    pub const fn hold(beat: f32, length: f32) -> Self {
+        Self {
+            beat,
+            kind: NoteKind::Hold { length },
+        }
+    }
+
+    pub const fn drag(beat: f32, length: f32) -> Self {
+        Self {
+            beat,
+            kind: NoteKind::Drag { length },
+        }
+    }
+
+    /// Returns how many beats after `beat` the note should be released, if it
+    /// is sustained.
+    pub fn length(&self) -> Option<f32> {
+        match self.kind {
+            NoteKind::Tap => None,
+            NoteKind::Hold { length } | NoteKind::Drag { length } => Some(length),
+        }
+    }
+}
+
+/// The time signature of a loop. Every loop uses quarter notes as its beat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Meter {
+    pub beats_per_bar: usize,
+}
+
+impl Meter {
+    pub const COMMON_TIME: Meter = Meter { beats_per_bar: 4 };
+
+    /// Returns true if `beat` falls on the first beat of a bar.
+    pub fn is_downbeat(&self, beat: f32) -> bool {
+        beat.fract().abs() < f32::EPSILON && beat as usize % self.beats_per_bar == 0
+    }
+}
+
+/// How busy a loop sounds. Used to keep the mix from getting cluttered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Energy {
+    Low,
+    Medium,
+    High,
+}
+
+impl Energy {
+    pub fn level(self) -> u32 {
+        match self {
+            Energy::Low => 1,
+            Energy::Medium => 2,
+            Energy::High => 3,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Loop {
+    pub name: &'static str,
     pub kind: LoopKind,
-    pub beats: Vec<f32>,
-    pub source: Buffered<Decoder<Cursor<&'static [u8]>>>,
+    pub energy: Energy,
+    /// Length of the loop, in beats. Loops shorter than a measure restart
+    /// within it, so a 16 beat loop plays twice per 32 beat measure. Only the
+    /// first `length` beats of the audio are played, so a short loop can be
+    /// cut from a longer recording that repeats.
+    pub length: usize,
+    pub meter: Meter,
+    /// Names of loops that clash with this one and shouldn't be played together
+    pub incompatible_with: &'static [&'static str],
+    pub beats: Vec<Note>,
+    /// The encoded audio, decoded by `assets::load`
+    pub audio: &'static [u8],
+    source: OnceCell<Buffered<Decoder<Cursor<&'static [u8]>>>>,
 }
 
 impl Loop {
-    fn create_source(bytes: &'static [u8]) -> Buffered<Decoder<Cursor<&'static [u8]>>> {
-        let source = rodio::Decoder::new(Cursor::new(bytes)).unwrap();
-        source.buffered()
+    pub fn decode(bytes: &'static [u8]) -> Result<Decoder<Cursor<&'static [u8]>>, DecoderError> {
+        rodio::Decoder::new(Cursor::new(bytes))
+    }
+
+    pub fn source(&self) -> &Buffered<Decoder<Cursor<&'static [u8]>>> {
+        self.source.get().expect(NOT_LOADED)
     }
 
     pub fn all() -> &'static Vec<Loop> {
@@ -108,136 +292,328 @@ impl Loop {
         LOOPS.get_or_init(|| {
             vec![
                 Loop {
+                    name: "Pads1",
                     kind: LoopKind::PADs,
+                    energy: Energy::Low,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
                     beats: Vec::default(),
-                    source: Self::create_source(include_bytes!("../assets/pxzel/space/Pads1.ogg")),
+                    audio: include_bytes!("../assets/pxzel/space/Pads1.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Pads2",
                     kind: LoopKind::PADs,
+                    energy: Energy::Low,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
                     beats: Vec::default(),
-                    source: Self::create_source(include_bytes!("../assets/pxzel/space/Pads2.ogg")),
+                    audio: include_bytes!("../assets/pxzel/space/Pads2.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Pads2_complex",
                     kind: LoopKind::PADs,
-                    beats: Vec::default(),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/Pads2_complex.ogg"
-                    )),
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    // The pads' sustained chords are dragged from one element to another,
+                    // while the plain pads play behind everything without any beats
+                    beats: Self::repeat_note_pattern(&[Note::drag(0., 3.)], 8, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Pads2_complex.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Arp_es",
                     kind: LoopKind::ARPs,
-                    beats: Self::repeat_beat_pattern(&[0., 0.5], 1),
-                    source: Self::create_source(include_bytes!("../assets/pxzel/space/Arp_es.ogg")),
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0., 0.5], 1, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Arp_es.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Arp_fths",
                     kind: LoopKind::ARPs,
-                    beats: Self::repeat_beat_pattern(&[0.], 1),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/Arp_fths.ogg"
-                    )),
+                    energy: Energy::Low,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0.], 1, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Arp_fths.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Arp_u_p",
                     kind: LoopKind::ARPs,
-                    beats: Self::repeat_beat_pattern(&[0., 0.5], 1),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/Arp_u_p.ogg"
-                    )),
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0., 0.5], 1, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Arp_u_p.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "lead1_simple",
                     kind: LoopKind::Leads,
+                    energy: Energy::Low,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
                     beats: Vec::default(),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/lead1_simple.ogg"
-                    )),
+                    audio: include_bytes!("../assets/pxzel/space/lead1_simple.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "lead1_complex",
                     kind: LoopKind::Leads,
+                    energy: Energy::High,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &["Bass_ft", "Bass_qt", "Arp_u_p"],
                     beats: Vec::default(),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/lead1_complex.ogg"
-                    )),
+                    audio: include_bytes!("../assets/pxzel/space/lead1_complex.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "lead1_med",
                     kind: LoopKind::Leads,
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
                     beats: Vec::default(),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/lead1_med.ogg"
-                    )),
+                    audio: include_bytes!("../assets/pxzel/space/lead1_med.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "lead2_med",
                     kind: LoopKind::Leads,
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
                     beats: Vec::default(),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/lead2_med.ogg"
-                    )),
+                    audio: include_bytes!("../assets/pxzel/space/lead2_med.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "lead2_simple",
                     kind: LoopKind::Leads,
+                    energy: Energy::Low,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
                     beats: Vec::default(),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/lead2_simple.ogg"
-                    )),
+                    audio: include_bytes!("../assets/pxzel/space/lead2_simple.ogg"),
+                    source: OnceCell::new(),
+                },
+                Loop {
+                    name: "Drums_ks",
+                    kind: LoopKind::Drums(DrumPart::KickSnare),
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0., 1., 2.5, 3.], 4, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Drums_ks.ogg"),
+                    source: OnceCell::new(),
+                },
+                Loop {
+                    name: "Drums_hh",
+                    kind: LoopKind::Drums(DrumPart::Hats),
+                    energy: Energy::Low,
+                    // The hats repeat every beat, so half the recording loops cleanly
+                    length: 16,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0., 0.5], 1, 16),
+                    audio: include_bytes!("../assets/pxzel/space/Drums_hh.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
-                    kind: LoopKind::Drums,
-                    beats: Self::repeat_beat_pattern(&[0.], 1),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/Drums_hh2.ogg"
-                    )),
+                    name: "Drums_hh2",
+                    kind: LoopKind::Drums(DrumPart::Hats),
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0.], 1, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Drums_hh2.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Drums_perc",
+                    kind: LoopKind::Drums(DrumPart::Percussion),
+                    energy: Energy::Low,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0., 1.5, 2.75], 4, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Drums_perc.ogg"),
+                    source: OnceCell::new(),
+                },
+                Loop {
+                    name: "Bass_ft",
                     kind: LoopKind::Bass,
-                    beats: Self::repeat_beat_pattern(&[0., 0.75, 1.5, 2.25, 3.0], 4),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/Bass_ft.ogg"
-                    )),
+                    energy: Energy::High,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(
+                        &[0., 0.75, 1.5, 2.25, 3.0],
+                        4,
+                        BEATS_PER_LOOP,
+                    ),
+                    audio: include_bytes!("../assets/pxzel/space/Bass_ft.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Bass_oct",
                     kind: LoopKind::Bass,
-                    beats: Self::repeat_beat_pattern(&[0., 0.5], 1),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/Bass_oct.ogg"
-                    )),
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0., 0.5], 1, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Bass_oct.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Bass_qt",
                     kind: LoopKind::Bass,
-                    beats: Self::repeat_beat_pattern(&[0., 1., 2., 3., 3.33, 3.66], 4),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/Bass_qt.ogg"
-                    )),
+                    energy: Energy::High,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(
+                        &[0., 1., 2., 3., 3.33, 3.66],
+                        4,
+                        BEATS_PER_LOOP,
+                    ),
+                    audio: include_bytes!("../assets/pxzel/space/Bass_qt.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Bass_sus",
                     kind: LoopKind::Bass,
-                    beats: Self::repeat_beat_pattern(&[0.], 4),
-                    source: Self::create_source(include_bytes!(
-                        "../assets/pxzel/space/Bass_sus.ogg"
-                    )),
+                    energy: Energy::Low,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_note_pattern(&[Note::hold(0., 3.)], 4, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Bass_sus.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Piano1",
                     kind: LoopKind::Piano,
-                    beats: Self::repeat_beat_pattern(&[0.], 4),
-                    source: Self::create_source(include_bytes!("../assets/pxzel/space/Piano1.ogg")),
+                    energy: Energy::Low,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0.], 4, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Piano1.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Piano2",
                     kind: LoopKind::Piano,
-                    beats: Self::repeat_beat_pattern(&[0., 1.75], 4),
-                    source: Self::create_source(include_bytes!("../assets/pxzel/space/Piano2.ogg")),
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0., 1.75], 4, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Piano2.ogg"),
+                    source: OnceCell::new(),
                 },
                 Loop {
+                    name: "Piano3",
                     kind: LoopKind::Piano,
-                    beats: Self::repeat_beat_pattern(&[0., 1.75, 3.], 4),
-                    source: Self::create_source(include_bytes!("../assets/pxzel/space/Piano3.ogg")),
+                    energy: Energy::Medium,
+                    length: BEATS_PER_LOOP,
+                    meter: Meter::COMMON_TIME,
+                    incompatible_with: &[],
+                    beats: Self::repeat_beat_pattern(&[0., 1.75, 3.], 4, BEATS_PER_LOOP),
+                    audio: include_bytes!("../assets/pxzel/space/Piano3.ogg"),
+                    source: OnceCell::new(),
                 },
             ]
         })
     }
 
-    fn repeat_beat_pattern(beats: &[f32], beat_pattern_length: usize) -> Vec<f32> {
-        let number_of_chunks = 32 / beat_pattern_length;
-        (1usize..number_of_chunks)
+    /// Which repetition of the loop is playing at `absolute_beat`, counted from
+    /// the start of the song, and how many beats into it the song is.
+    pub fn position(&self, absolute_beat: f32) -> (usize, f32) {
+        let length = self.length.max(1) as f32;
+        let absolute_beat = absolute_beat.max(0.);
+        let iteration = (absolute_beat / length) as usize;
+        (iteration, absolute_beat - iteration as f32 * length)
+    }
+
+    /// Returns true if this loop can play alongside `other` without clashing.
+    /// Every pitched loop in a pack is written in the same key, so it's only
+    /// busy parts that clash.
+    pub fn is_compatible_with(&self, other: &Loop) -> bool {
+        !self.incompatible_with.contains(&other.name)
+            && !other.incompatible_with.contains(&self.name)
+    }
+
+    /// Returns true if this loop is compatible with every loop in `playing`
+    /// and adding it keeps the combined energy within `MAX_ENERGY`.
+    pub fn fits_with<'a, I: IntoIterator<Item = &'a Loop>>(&self, playing: I) -> bool {
+        let mut energy = self.energy.level();
+        for other in playing {
+            if !self.is_compatible_with(other) {
+                return false;
+            }
+            energy += other.energy.level();
+        }
+
+        energy <= MAX_ENERGY
+    }
+
+    /// The loops that could be spawned as elements alongside `playing`: ones
+    /// with beats to hit, of a kind that no other element is playing, that fit
+    /// with everything that is. Loops without beats, like the pads behind
+    /// everything, don't take up their kind.
+    pub fn spawnable(playing: &[&'static Loop]) -> impl Iterator<Item = &'static Loop> + '_ {
+        Loop::all().iter().filter(move |l| {
+            !l.beats.is_empty()
+                && !playing
+                    .iter()
+                    .any(|other| !other.beats.is_empty() && other.kind == l.kind)
+                && l.fits_with(playing.iter().copied())
+        })
+    }
+
+    fn repeat_beat_pattern(
+        beats: &[f32],
+        beat_pattern_length: usize,
+        loop_length: usize,
+    ) -> Vec<Note> {
+        let notes = beats.iter().copied().map(Note::tap).collect::<Vec<_>>();
+        Self::repeat_note_pattern(&notes, beat_pattern_length, loop_length)
+    }
+
+    fn repeat_note_pattern(notes: &[Note], pattern_length: usize, loop_length: usize) -> Vec<Note> {
+        let number_of_chunks = loop_length / pattern_length;
+        (0..number_of_chunks)
             .map(|chunk| {
-                let offset = (chunk * beat_pattern_length) as f32;
-                beats.iter().map(|beat| beat + offset).collect::<Vec<_>>()
+                let offset = (chunk * pattern_length) as f32;
+                notes
+                    .iter()
+                    .map(|note| Note {
+                        beat: note.beat + offset,
+                        ..*note
+                    })
+                    .collect::<Vec<_>>()
             })
             .flatten()
             .collect()
@@ -246,3 +622,113 @@ impl Loop {
 
 pub const BEATS_PER_LOOP: usize = 32;
 pub const TEMPO: f32 = 83.;
+/// The most combined energy that can be playing at once.
+pub const MAX_ENERGY: u32 = 12;
+
+#[cfg(test)]
+impl Loop {
+    /// The loop called `name` in the space pack.
+    pub fn named(name: &str) -> &'static Loop {
+        Loop::all()
+            .iter()
+            .find(|audio_loop| audio_loop.name == name)
+            .unwrap()
+    }
+
+    /// A loop outside of any pack, `length` beats long, with `pattern`
+    /// repeated every `pattern_length` beats to fill it. The packs don't have
+    /// any loops longer than a measure so far, so this covers those.
+    pub fn repeating(length: usize, pattern: &[f32], pattern_length: usize) -> &'static Loop {
+        Box::leak(Box::new(Loop {
+            name: "test",
+            kind: LoopKind::Piano,
+            energy: Energy::Low,
+            length,
+            meter: Meter::COMMON_TIME,
+            incompatible_with: &[],
+            beats: Self::repeat_beat_pattern(pattern, pattern_length, length),
+            audio: &[],
+            source: OnceCell::new(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beats(audio_loop: &Loop) -> Vec<f32> {
+        audio_loop.beats.iter().map(|note| note.beat).collect()
+    }
+
+    fn spawnable(playing: &[&str]) -> Vec<&'static str> {
+        let playing = playing
+            .iter()
+            .map(|name| Loop::named(name))
+            .collect::<Vec<_>>();
+        Loop::spawnable(&playing).map(|l| l.name).collect()
+    }
+
+    #[test]
+    fn spawning_skips_loops_that_clash() {
+        let alongside_pads = spawnable(&["Pads1"]);
+        assert!(alongside_pads.contains(&"Bass_ft"));
+        assert!(alongside_pads.contains(&"Arp_u_p"));
+        // The pads behind everything leave room for pads to drag between elements
+        assert!(alongside_pads.contains(&"Pads2_complex"));
+        assert!(!spawnable(&["Pads1", "Pads2_complex"]).contains(&"Pads2_complex"));
+
+        // The complex lead is too busy for the busier bass lines and arps
+        let alongside_lead = spawnable(&["Pads1", "lead1_complex"]);
+        assert!(!alongside_lead.contains(&"Bass_ft"));
+        assert!(!alongside_lead.contains(&"Bass_qt"));
+        assert!(!alongside_lead.contains(&"Arp_u_p"));
+        assert!(alongside_lead.contains(&"Bass_oct"));
+        assert!(alongside_lead.contains(&"Arp_es"));
+    }
+
+    #[test]
+    fn spawning_skips_kinds_already_playing() {
+        let alongside_bass = spawnable(&["Pads1", "Bass_oct"]);
+        assert!(alongside_bass
+            .iter()
+            .all(|name| Loop::named(name).kind != LoopKind::Bass));
+        assert!(alongside_bass.contains(&"Piano2"));
+    }
+
+    #[test]
+    fn patterns_fill_short_loops() {
+        let hats = Loop::named("Drums_hh");
+        assert_eq!(hats.length, 16);
+        assert_eq!(hats.beats.len(), 32);
+        assert_eq!(hats.beats.last().unwrap().beat, 15.5);
+    }
+
+    #[test]
+    fn short_loops_repeat_within_a_measure() {
+        let hats = Loop::named("Drums_hh");
+        assert_eq!(hats.position(0.), (0, 0.));
+        assert_eq!(hats.position(15.5), (0, 15.5));
+        assert_eq!(hats.position(16.), (1, 0.));
+        // Halfway through the second measure is the start of the fourth
+        // repetition of the hats
+        assert_eq!(hats.position(BEATS_PER_LOOP as f32 * 1.5), (3, 0.));
+        assert_eq!(hats.position(BEATS_PER_LOOP as f32 + 4.25), (2, 4.25));
+    }
+
+    #[test]
+    fn patterns_fill_long_loops() {
+        let audio_loop = Loop::repeating(64, &[0., 2.], 16);
+        assert_eq!(
+            beats(audio_loop),
+            vec![0., 2., 16., 18., 32., 34., 48., 50.]
+        );
+    }
+
+    #[test]
+    fn patterns_only_repeat_whole() {
+        // A pattern that doesn't divide the loop stops before the loop ends
+        let audio_loop = Loop::repeating(8, &[0.], 3);
+        assert_eq!(beats(audio_loop), vec![0., 3.]);
+    }
+}
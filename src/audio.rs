@@ -0,0 +1,215 @@
+use crate::{
+    assets::{self, Loop},
+    beats_per_second, seconds_per_beat, settings,
+    tempo::{self, PlaybackRateExt},
+};
+use once_cell::sync::Lazy;
+use rodio::{Device, DeviceTrait, Sample, Sink, Source};
+use std::{
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// How often to look for the output device changing.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Bumped each time the output device changes, so voices know to move over.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+/// The device voices are playing on. Looking through the devices can be slow,
+/// so it's only done when checking for changes.
+static ACTIVE_DEVICE: Lazy<Mutex<Option<Device>>> = Lazy::new(|| Mutex::new(find_device()));
+
+/// The names of every output device, for choosing between them.
+pub fn device_names() -> Vec<String> {
+    rodio::output_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// The device to play on.
+pub fn output_device() -> Option<Device> {
+    ACTIVE_DEVICE.lock().unwrap().clone()
+}
+
+/// The device chosen in the settings, or the system default if it isn't
+/// connected.
+fn find_device() -> Option<Device> {
+    if let Some(name) = settings::current().audio_device {
+        let chosen = rodio::output_devices().ok().and_then(|mut devices| {
+            devices.find(|device| device.name().ok().as_ref() == Some(&name))
+        });
+        if chosen.is_some() {
+            return chosen;
+        }
+    }
+    rodio::default_output_device()
+}
+
+/// The device after `current` in `names`, for cycling through them with a
+/// toggle. `None` is the system default, and comes before the first device.
+pub fn next_device(current: Option<&str>, names: &[String]) -> Option<String> {
+    let next_index = match current {
+        Some(current) => names
+            .iter()
+            .position(|name| name == current)
+            .map(|index| index + 1)
+            .unwrap_or_default(),
+        None => 0,
+    };
+    names.get(next_index).cloned()
+}
+
+/// Checks whether the device voices should play on has changed, because the
+/// chosen device was connected or disconnected or a different one was chosen.
+pub fn check_device() {
+    let device = find_device();
+    let mut active = ACTIVE_DEVICE.lock().unwrap();
+    if name_of(&active) != name_of(&device) {
+        tracing::info!(from = ?name_of(&active), to = ?name_of(&device), "output device changed");
+        *active = device;
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn name_of(device: &Option<Device>) -> Option<String> {
+    device.as_ref().and_then(|device| device.name().ok())
+}
+
+/// Checks for the output device changing in the background.
+pub fn watch_devices() {
+    std::thread::spawn(|| loop {
+        std::thread::sleep(DEVICE_CHECK_INTERVAL);
+        check_device();
+    });
+}
+
+fn generation() -> usize {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// A sink that remembers which device it was created on, so it can be moved
+/// when the device changes.
+pub struct Voice {
+    sink: Arc<Sink>,
+    generation: usize,
+}
+
+impl Voice {
+    pub fn new() -> Option<Self> {
+        let generation = generation();
+        let device = output_device()?;
+        tracing::debug!(generation, device = ?device.name().ok(), "creating sink");
+        Some(Self {
+            sink: Arc::new(Sink::new(&device)),
+            generation,
+        })
+    }
+
+    /// Starts playing `audio_loop` once, `beat` beats in, stopping at the end
+    /// of the loop even if the recording goes on.
+    pub fn play(audio_loop: &Loop, beat: f32, volume: f32) -> Option<Self> {
+        let voice = Self::new()?;
+        voice.set_volume(volume);
+        let length = audio_loop.length as f32;
+        voice.skip_then(
+            audio_loop.source().clone(),
+            beat,
+            move |sink, source, beat| {
+                let remaining = (length - beat).max(0.) * seconds_per_beat(assets::TEMPO);
+                sink.append(
+                    source
+                        .take_duration(Duration::from_secs_f32(remaining))
+                        .at_playback_rate(),
+                );
+            },
+        );
+        Some(voice)
+    }
+
+    /// Skips `beats` beats into `source`, then hands it to `play` along with
+    /// how many beats were skipped in all. Skipping deep into a loop is slow,
+    /// so it's done on a worker thread rather than holding up the frame, and
+    /// the time it takes is skipped as well so the source still starts where
+    /// it should. Nothing is played if the voice is dropped first.
+    pub fn skip_then<S, F>(&self, source: S, beats: f32, play: F)
+    where
+        S: Source + Send + 'static,
+        S::Item: Sample,
+        F: FnOnce(&Sink, S, f32) + Send + 'static,
+    {
+        if beats <= 0. {
+            play(&self.sink, source, 0.);
+            return;
+        }
+
+        let sink = Arc::downgrade(&self.sink);
+        let requested = Instant::now();
+        std::thread::spawn(move || {
+            let source = skip_beats(source, beats);
+            let tempo = assets::TEMPO * tempo::playback_rate();
+            let late = requested.elapsed().as_secs_f32() * beats_per_second(tempo);
+            let source = skip_beats(source, late);
+            if let Some(sink) = sink.upgrade() {
+                play(&sink, source, beats + late);
+            }
+        });
+    }
+
+    /// Returns true if the output device has changed since this voice was
+    /// created.
+    pub fn is_stale(&self) -> bool {
+        self.generation != generation()
+    }
+}
+
+impl Deref for Voice {
+    type Target = Sink;
+
+    fn deref(&self) -> &Sink {
+        &self.sink
+    }
+}
+
+/// Skips the first `beats` beats of `source`, so that a voice moved to a new
+/// device picks up where it left off. This decodes everything it skips, so use
+/// `Voice::skip_then` to keep it off the UI thread.
+fn skip_beats<S>(mut source: S, beats: f32) -> S
+where
+    S: Source,
+    S::Item: Sample,
+{
+    let seconds = beats.max(0.) * seconds_per_beat(assets::TEMPO);
+    let frames = (seconds as f64 * source.sample_rate() as f64) as usize;
+    for _ in 0..frames * source.channels() as usize {
+        if source.next().is_none() {
+            break;
+        }
+    }
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycling_through_devices() {
+        let names = vec!["Speakers".to_string(), "Headphones".to_string()];
+        assert_eq!(next_device(None, &names), Some("Speakers".to_string()));
+        assert_eq!(
+            next_device(Some("Speakers"), &names),
+            Some("Headphones".to_string())
+        );
+        assert_eq!(next_device(Some("Headphones"), &names), None);
+        // A device that's been unplugged starts over from the first device
+        assert_eq!(
+            next_device(Some("Bluetooth"), &names),
+            Some("Speakers".to_string())
+        );
+        assert_eq!(next_device(None, &[]), None);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{
+    assets::{DrumPart, Loop, LoopKind},
+    cursor::BeatCursor,
+    random,
+    theme::{BackdropStyle, StarLayer},
+};
+use kludgine::prelude::*;
+use rand::prelude::*;
+use std::time::Instant;
+
+/// The scenery behind the game, which drifts and pulses along with the music.
+pub struct Backdrop {
+    style: BackdropStyle,
+    image: Entity<Image>,
+    tint: Entity<Tint>,
+    stars: Vec<Star>,
+    started: Instant,
+    last_update: Instant,
+    absolute_beat: f32,
+    /// Walks through the kick drum's beat map, so the pulses land on kicks
+    kicks: Option<BeatCursor>,
+    upcoming_kick: Option<f32>,
+    last_kick: Option<f32>,
+    active_kinds: Vec<LoopKind>,
+    tint_color: [f32; 4],
+}
+
+/// How many beats a pulse takes to fade after a kick.
+const PULSE_DECAY_BEATS: f32 = 1.;
+
+struct Star {
+    image: Entity<Image>,
+    layer: StarLayer,
+    /// Position as a fraction of the scene's size
+    x: f32,
+    y: f32,
+}
+
+#[derive(Clone, Debug)]
+pub enum BackdropCommand {
+    SetBeat(f32),
+    SetActiveKinds(Vec<LoopKind>),
+}
+
+impl Backdrop {
+    pub fn new(style: BackdropStyle) -> Self {
+        let now = Instant::now();
+        Self {
+            style,
+            image: Default::default(),
+            tint: Default::default(),
+            stars: Vec::default(),
+            started: now,
+            last_update: now,
+            absolute_beat: 0.,
+            kicks: Loop::all()
+                .iter()
+                .find(|l| l.kind == LoopKind::Drums(DrumPart::KickSnare))
+                .map(BeatCursor::new),
+            upcoming_kick: None,
+            last_kick: None,
+            active_kinds: Vec::default(),
+            tint_color: [0.; 4],
+        }
+    }
+
+    fn set_beat(&mut self, absolute_beat: f32) {
+        self.absolute_beat = absolute_beat;
+        if let Some(kicks) = self.kicks.as_mut() {
+            // A new kick is only returned once the one before it has passed
+            if let Some(upcoming) = kicks.advance(absolute_beat) {
+                if let Some(passed) = self.upcoming_kick.replace(upcoming.absolute_beat) {
+                    self.last_kick = Some(passed);
+                }
+            }
+        }
+    }
+
+    /// How much brighter the backdrop should be right now, peaking on each
+    /// kick and decaying after it.
+    fn pulse(&self) -> f32 {
+        let since_kick = match self.last_kick {
+            Some(last_kick) => self.absolute_beat - last_kick,
+            None => return 0.,
+        };
+        let decay = (1. - since_kick / PULSE_DECAY_BEATS).max(0.).powi(3);
+        // The kick is much more noticeable when the kick drum is playing
+        let strength = if self
+            .active_kinds
+            .contains(&LoopKind::Drums(DrumPart::KickSnare))
+        {
+            self.style.pulse_strength
+        } else {
+            self.style.pulse_strength / 2.
+        };
+        decay * strength
+    }
+
+    /// How bright the backdrop texture is between kicks. It's dimmed by the
+    /// pulse strength so that each kick can brighten it to full.
+    fn resting_brightness(&self) -> f32 {
+        1. - self.style.pulse_strength
+    }
+
+    fn target_tint(&self, pulse: f32) -> [f32; 4] {
+        let colors = self
+            .active_kinds
+            .iter()
+            .filter_map(|kind| self.style.color_for(kind))
+            .collect::<Vec<_>>();
+        if colors.is_empty() {
+            return [0.; 4];
+        }
+
+        let mut tint = [0., 0., 0., self.style.tint_alpha * (1. + pulse)];
+        for color in &colors {
+            for (channel, value) in color.iter().enumerate() {
+                tint[channel] += value / colors.len() as f32;
+            }
+        }
+        tint
+    }
+}
+
+#[async_trait]
+impl Component for Backdrop {
+    async fn initialize(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
+        self.image = self
+            .new_entity(
+                context,
+                Image::new(self.style.image.clone()).options(
+                    ImageOptions::default()
+                        .scaling(ImageScaling::AspectFill)
+                        .alpha(self.resting_brightness()),
+                ),
+            )
+            .insert()
+            .await?;
+
+        self.tint = self.new_entity(context, Tint::default()).insert().await?;
+
+        for layer in self.style.star_layers.clone() {
+            for _ in 0..layer.count {
+                let image = self
+                    .new_entity(
+                        context,
+                        Image::new(self.style.star.clone())
+                            .options(ImageOptions::default().alpha(layer.alpha)),
+                    )
+                    .insert()
+                    .await?;
+                let (x, y) = {
+                    let mut rng = random::rng();
+                    (rng.gen_range(0., 1.), rng.gen_range(0., 1.))
+                };
+                self.stars.push(Star { image, layer, x, y });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn layout(
+        &mut self,
+        context: &mut StyledContext,
+    ) -> KludgineResult<Box<dyn LayoutSolver>> {
+        let scene_size = context.scene().size().await.to_f32();
+        let elapsed = self.started.elapsed().as_secs_f32();
+
+        let mut layout = Layout::absolute()
+            .child(
+                &self.image,
+                AbsoluteBounds::from(Surround::uniform(Dimension::from_points(0.))),
+            )?
+            .child(
+                &self.tint,
+                AbsoluteBounds::from(Surround::uniform(Dimension::from_points(0.))),
+            )?;
+
+        for star in &self.stars {
+            // Nearer layers drift faster, wrapping around the edge of the scene
+            let x = (star.x + star.layer.speed * elapsed).fract();
+            layout = layout.child(
+                &star.image,
+                AbsoluteBounds {
+                    left: Dimension::from_points(x * scene_size.width),
+                    top: Dimension::from_points(star.y * scene_size.height),
+                    ..Default::default()
+                },
+            )?;
+        }
+
+        layout.layout()
+    }
+
+    async fn update(&mut self, _context: &mut SceneContext) -> KludgineResult<()> {
+        let now = Instant::now();
+        let elapsed = now
+            .checked_duration_since(self.last_update)
+            .unwrap_or_default()
+            .as_secs_f32();
+        self.last_update = now;
+
+        let pulse = self.pulse();
+        self.image
+            .send(ImageCommand::SetAlpha(
+                (self.resting_brightness() + pulse).min(1.),
+            ))
+            .await?;
+        for star in &self.stars {
+            star.image
+                .send(ImageCommand::SetAlpha(
+                    (star.layer.alpha * (1. + pulse)).min(1.),
+                ))
+                .await?;
+        }
+
+        // Ease towards the new color over roughly half a second
+        let target = self.target_tint(pulse);
+        let blend = 1. - (-elapsed * 4.).exp();
+        for (channel, value) in self.tint_color.iter_mut().enumerate() {
+            *value += (target[channel] - *value) * blend;
+        }
+        let [red, green, blue, alpha] = self.tint_color;
+        self.tint
+            .send(TintCommand::SetColor(Color::new(red, green, blue, alpha)))
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InteractiveComponent for Backdrop {
+    type Message = ();
+    type Input = BackdropCommand;
+    type Output = ();
+
+    async fn receive_input(
+        &mut self,
+        _context: &mut Context,
+        command: Self::Input,
+    ) -> KludgineResult<()> {
+        match command {
+            BackdropCommand::SetBeat(absolute_beat) => {
+                self.set_beat(absolute_beat);
+            }
+            BackdropCommand::SetActiveKinds(kinds) => {
+                self.active_kinds = kinds;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A translucent wash of color over the backdrop.
+#[derive(Default)]
+pub struct Tint {
+    color: Option<Color>,
+}
+
+#[derive(Clone, Debug)]
+pub enum TintCommand {
+    SetColor(Color),
+}
+
+#[async_trait]
+impl Component for Tint {
+    async fn render(&self, context: &mut StyledContext, layout: &Layout) -> KludgineResult<()> {
+        if let Some(color) = self.color {
+            Shape::rect(layout.inner_bounds())
+                .fill(Fill::new(color))
+                .render_at(Point::default(), context.scene())
+                .await;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InteractiveComponent for Tint {
+    type Message = ();
+    type Input = TintCommand;
+    type Output = ();
+
+    async fn receive_input(
+        &mut self,
+        _context: &mut Context,
+        command: Self::Input,
+    ) -> KludgineResult<()> {
+        match command {
+            TintCommand::SetColor(color) => {
+                self.color = Some(color);
+            }
+        }
+        Ok(())
+    }
+}
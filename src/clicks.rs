@@ -1,35 +1,57 @@
+use crate::{palette, timing};
 use kludgine::prelude::*;
 use std::time::Instant;
 
-#[derive(Default)]
 pub struct Clicks {
+    sprite: Sprite,
     clicks: Entity<Image>,
+    /// Shows how early or late the click was
+    offset: Entity<Label>,
+    /// Marks hits and misses with shapes in the palette's colors, in place of
+    /// the sprite, in visual modes that ask for them
+    marker: Entity<HitMarker>,
     location: Point<Points>,
     last_click: Option<Instant>,
 }
 
+impl Clicks {
+    pub fn new(sprite: Sprite) -> Self {
+        Self {
+            sprite,
+            clicks: Default::default(),
+            offset: Default::default(),
+            marker: Default::default(),
+            location: Default::default(),
+            last_click: None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum ClickCommand {
     SetStatus {
         success: bool,
         location: Option<Point<Points>>,
+        /// How far from the beat the click landed, in milliseconds
+        offset_millis: Option<i64>,
     },
 }
 
 #[async_trait]
 impl Component for Clicks {
     async fn initialize(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
-        let clicks = include_aseprite_sprite!("../assets/whitevault/space/clicks")
-            .await
-            .unwrap();
-
         self.clicks = self
             .new_entity(
                 context,
-                Image::new(clicks).options(ImageOptions::default().alpha(0.0)),
+                Image::new(self.sprite.clone()).options(ImageOptions::default().alpha(0.0)),
             )
             .insert()
             .await?;
+        self.offset = self.new_entity(context, Label::new("")).insert().await?;
+        self.marker = self
+            .new_entity(context, HitMarker::default())
+            .insert()
+            .await?;
         Ok(())
     }
     async fn layout(
@@ -45,6 +67,24 @@ impl Component for Clicks {
                     ..Default::default()
                 },
             )?
+            .child(
+                &self.offset,
+                AbsoluteBounds {
+                    left: Dimension::from_points(self.location.x),
+                    top: Dimension::from_points(self.location.y + Points::from_f32(52.)),
+                    ..Default::default()
+                },
+            )?
+            .child(
+                &self.marker,
+                AbsoluteBounds {
+                    left: Dimension::from_points(self.location.x + Points::from_f32(8.)),
+                    top: Dimension::from_points(self.location.y + Points::from_f32(8.)),
+                    width: Dimension::from_points(MARKER_SIZE),
+                    height: Dimension::from_points(MARKER_SIZE),
+                    ..Default::default()
+                },
+            )?
             .layout()
     }
 
@@ -54,6 +94,10 @@ impl Component for Clicks {
                 if duration.as_millis() > 250 {
                     self.clicks.send(ImageCommand::SetTag(None)).await?;
                     self.clicks.send(ImageCommand::SetAlpha(0.)).await?;
+                    self.offset
+                        .send(LabelCommand::SetValue(String::default()))
+                        .await?;
+                    self.marker.send(HitMarkerCommand::SetHit(None)).await?;
                 }
             }
         }
@@ -73,24 +117,101 @@ impl InteractiveComponent for Clicks {
         command: Self::Input,
     ) -> KludgineResult<()> {
         match command {
-            ClickCommand::SetStatus { success, location } => {
+            ClickCommand::SetStatus {
+                success,
+                location,
+                offset_millis,
+            } => {
                 if let Some(location) = location {
                     self.location =
                         location - Point::new(Points::from_f32(24.), Points::from_f32(48.));
                     self.last_click = Some(Instant::now());
-                    self.clicks.send(ImageCommand::SetAlpha(1.)).await?;
-                    if success {
-                        self.clicks
-                            .send(ImageCommand::SetTag(Some("Yes".to_string())))
+                    if palette::current().shapes {
+                        self.marker
+                            .send(HitMarkerCommand::SetHit(Some(success)))
                             .await?;
                     } else {
+                        let tag = if success { "Yes" } else { "No" };
+                        self.clicks.send(ImageCommand::SetAlpha(1.)).await?;
                         self.clicks
-                            .send(ImageCommand::SetTag(Some("No".to_string())))
+                            .send(ImageCommand::SetTag(Some(tag.to_string())))
                             .await?;
                     }
+                    let offset = offset_millis
+                        .map(timing::describe_offset)
+                        .unwrap_or_default();
+                    self.offset.send(LabelCommand::SetValue(offset)).await?;
                 }
             }
         }
         Ok(())
     }
 }
+
+const MARKER_SIZE: f32 = 32.;
+
+/// A circle for a hit or a square for a miss, so that the two can be told apart
+/// without relying on color.
+#[derive(Default)]
+pub struct HitMarker {
+    hit: Option<bool>,
+}
+
+impl HitMarker {
+    pub fn new(hit: bool) -> Self {
+        Self { hit: Some(hit) }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum HitMarkerCommand {
+    SetHit(Option<bool>),
+}
+
+#[async_trait]
+impl Component for HitMarker {
+    async fn render(&self, context: &mut StyledContext, layout: &Layout) -> KludgineResult<()> {
+        let palette = palette::current();
+        let hit = match self.hit {
+            Some(hit) if palette.shapes => hit,
+            _ => return Ok(()),
+        };
+
+        let bounds = layout.inner_bounds();
+        let shape = if hit {
+            let radius = bounds.size.width.to_f32().min(bounds.size.height.to_f32()) / 2.;
+            Shape::circle(
+                Point::new(
+                    Points::from_f32(bounds.origin.x.to_f32() + radius),
+                    Points::from_f32(bounds.origin.y.to_f32() + radius),
+                ),
+                Points::from_f32(radius),
+            )
+            .fill(Fill::new(palette.success))
+        } else {
+            Shape::rect(bounds).fill(Fill::new(palette.failure))
+        };
+        shape.render_at(Point::default(), context.scene()).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InteractiveComponent for HitMarker {
+    type Message = ();
+    type Input = HitMarkerCommand;
+    type Output = ();
+
+    async fn receive_input(
+        &mut self,
+        _context: &mut Context,
+        command: Self::Input,
+    ) -> KludgineResult<()> {
+        match command {
+            HitMarkerCommand::SetHit(hit) => {
+                self.hit = hit;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{assets, beats_per_second};
+use once_cell::sync::Lazy;
+use rodio::{Sample, Source};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// How long the measured drift is averaged over, in seconds. The audio device
+/// pulls samples in bursts, so single measurements are noisy.
+const SMOOTHING_SECONDS: f32 = 0.5;
+/// How much of the drift is corrected each second.
+const CORRECTION_PER_SECOND: f32 = 0.5;
+/// The most the conductor is sped up or slowed down by, in beats per second.
+const MAX_CORRECTION: f32 = 0.05;
+/// Drift smaller than this is left alone, in beats.
+const DEAD_ZONE: f32 = 0.005;
+
+/// How long samples wait in the output buffer before they're heard, in
+/// seconds. The device doesn't report this, so it's an estimate of a typical
+/// buffer; the audio clock runs ahead of what's heard by any difference.
+pub const OUTPUT_LATENCY_SECONDS: f32 = 0.05;
+
+/// Samples of the clocked source handed to the audio device since the clock
+/// was started. These are pulled ahead of being played, by the output latency.
+static PLAYED_SAMPLES: AtomicU64 = AtomicU64::new(0);
+static START: Lazy<Mutex<Option<ClockStart>>> = Lazy::new(Default::default);
+/// Bumped each time the clock starts or stops, so that sources from an earlier
+/// voice that are still being pulled don't move the clock.
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, Debug)]
+struct ClockStart {
+    beat: f32,
+    samples_per_second: f64,
+}
+
+/// Restarts the audio clock at `beat`, for a clocked source with the given
+/// format that's about to start playing.
+pub fn start(beat: f32, sample_rate: u32, channels: u16) {
+    let mut start = START.lock().unwrap();
+    EPOCH.fetch_add(1, Ordering::SeqCst);
+    PLAYED_SAMPLES.store(0, Ordering::SeqCst);
+    *start = Some(ClockStart {
+        beat,
+        samples_per_second: sample_rate as f64 * channels as f64,
+    });
+}
+
+/// Stops the audio clock, for when the voice keeping it is lost. The conductor
+/// runs on its own until the clock is started again.
+pub fn stop() {
+    let mut start = START.lock().unwrap();
+    EPOCH.fetch_add(1, Ordering::SeqCst);
+    *start = None;
+}
+
+/// How far into the song the audio has actually been heard, in beats, or None
+/// while the clock is stopped.
+pub fn audio_beat() -> Option<f32> {
+    let start = (*START.lock().unwrap())?;
+    let pulled = PLAYED_SAMPLES.load(Ordering::Relaxed) as f64 / start.samples_per_second;
+    let heard = (pulled as f32 - OUTPUT_LATENCY_SECONDS).max(0.);
+    Some(start.beat + heard * beats_per_second(assets::TEMPO))
+}
+
+/// Nudges the conductor towards the audio clock a little each frame, so that
+/// the visuals and the music stay in step over a long session without jumps.
+#[derive(Debug, Default)]
+pub struct DriftCorrector {
+    smoothed: Option<f32>,
+    stats: DriftStats,
+}
+
+/// How far the conductor has drifted from the audio, in beats. Positive when
+/// the conductor is ahead.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DriftStats {
+    /// The drift measured most recently
+    pub measured: f32,
+    /// The drift being corrected, averaged over recent measurements
+    pub smoothed: f32,
+    /// The largest smoothed drift seen
+    pub largest: f32,
+    /// How fast the conductor is being corrected, in beats per second
+    pub correction: f32,
+}
+
+impl DriftCorrector {
+    /// Records the drift measured this frame, `seconds` after the last one, and
+    /// returns how many beats to move the conductor by.
+    pub fn correct(&mut self, drift: f32, seconds: f32) -> f32 {
+        let smoothed = match self.smoothed {
+            Some(smoothed) => {
+                let weight = 1. - (-seconds / SMOOTHING_SECONDS).exp();
+                smoothed + (drift - smoothed) * weight
+            }
+            None => drift,
+        };
+
+        let correction = if smoothed.abs() < DEAD_ZONE || seconds <= 0. {
+            0.
+        } else {
+            let limit = MAX_CORRECTION * seconds;
+            (smoothed * CORRECTION_PER_SECOND * seconds)
+                .min(limit)
+                .max(-limit)
+        };
+        // The correction closes the gap, so the next measurement will be smaller
+        self.smoothed = Some(smoothed - correction);
+
+        self.stats = DriftStats {
+            measured: drift,
+            smoothed,
+            largest: if smoothed.abs() > self.stats.largest.abs() {
+                smoothed
+            } else {
+                self.stats.largest
+            },
+            correction: if seconds > 0. {
+                -correction / seconds
+            } else {
+                0.
+            },
+        };
+        -correction
+    }
+
+    /// Forgets the drift being corrected, for when the audio clock stops, so
+    /// that it starts afresh when the clock does.
+    pub fn pause(&mut self) {
+        self.smoothed = None;
+    }
+
+    /// How the drift has looked recently, for the debug overlay.
+    pub fn stats(&self) -> DriftStats {
+        self.stats
+    }
+}
+
+/// Counts the samples of a source as they're played, to keep the audio clock.
+/// Wrap the source before its playback rate changes, so that the clock follows
+/// the music rather than the wall clock.
+pub struct Clocked<I> {
+    input: I,
+    epoch: u64,
+}
+
+pub trait ClockedExt: Source + Sized
+where
+    Self::Item: Sample,
+{
+    fn clocked(self) -> Clocked<Self> {
+        Clocked {
+            input: self,
+            epoch: EPOCH.load(Ordering::SeqCst),
+        }
+    }
+}
+
+impl<I> ClockedExt for I
+where
+    I: Source,
+    I::Item: Sample,
+{
+}
+
+impl<I> Iterator for Clocked<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let sample = self.input.next();
+        if sample.is_some() && self.epoch == EPOCH.load(Ordering::Relaxed) {
+            PLAYED_SAMPLES.fetch_add(1, Ordering::Relaxed);
+        }
+        sample
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> Source for Clocked<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    const FRAME: f32 = 1. / 60.;
+
+    /// Plays `seconds` of frames with the conductor starting `offset` beats
+    /// ahead of the audio, returning how far apart they end up.
+    fn simulate(offset: f32, seconds: f32) -> (f32, DriftCorrector) {
+        let mut corrector = DriftCorrector::default();
+        let mut audio = 0.;
+        let mut conductor = offset;
+        for _ in 0..(seconds / FRAME) as usize {
+            audio += FRAME;
+            conductor += FRAME;
+            conductor += corrector.correct(conductor - audio, FRAME);
+        }
+        (conductor - audio, corrector)
+    }
+
+    #[test]
+    fn drift_is_corrected() {
+        let (ahead, _) = simulate(0.1, 10.);
+        assert!(ahead.abs() < 0.01, "still {} beats ahead", ahead);
+        let (behind, _) = simulate(-0.1, 10.);
+        assert!(behind.abs() < 0.01, "still {} beats behind", behind);
+    }
+
+    #[test]
+    fn corrections_are_gradual() {
+        let (drift, corrector) = simulate(1., 1.);
+        assert!(drift > 1. - MAX_CORRECTION * 1.01);
+        assert!(corrector.stats().correction >= -MAX_CORRECTION * 1.01);
+        assert!((corrector.stats().largest - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn stopped_clocks_report_nothing() {
+        start(0., 100, 1);
+        let old = SamplesBuffer::new(1, 100, vec![0i16; 50]).clocked();
+        stop();
+        assert_eq!(audio_beat(), None);
+
+        // Sources from before the clock stopped no longer move it
+        start(4., 100, 1);
+        assert_eq!(old.count(), 50);
+        assert_eq!(audio_beat(), Some(4.));
+    }
+
+    #[test]
+    fn small_drift_is_left_alone() {
+        let mut corrector = DriftCorrector::default();
+        assert_eq!(corrector.correct(DEAD_ZONE / 2., FRAME), 0.);
+    }
+}
@@ -0,0 +1,338 @@
+use crate::assets::{Loop, Note};
+
+/// A note coming up, placed on the song's timeline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpcomingNote {
+    pub note: Note,
+    /// The beat the note lands on, counted from the start of the song
+    pub absolute_beat: f32,
+    /// How many beats away the note is
+    pub beats_until: f32,
+}
+
+/// Walks through a loop's notes as the song plays. The loop repeats forever,
+/// so the notes of each repetition are placed one loop length after the last.
+#[derive(Clone)]
+pub struct BeatCursor {
+    audio_loop: &'static Loop,
+    /// The note after the last one that has passed, counted across repetitions
+    next: usize,
+    /// The last note returned by `advance`, so that no note is returned twice
+    returned: Option<usize>,
+}
+
+impl BeatCursor {
+    pub fn new(audio_loop: &'static Loop) -> Self {
+        Self {
+            audio_loop,
+            next: 0,
+            returned: None,
+        }
+    }
+
+    /// Where the `index`th note, counted across repetitions, lands on the
+    /// song's timeline.
+    fn note_at(&self, index: usize) -> (Note, f32) {
+        let notes = &self.audio_loop.beats;
+        let repetition = index / notes.len();
+        let note = notes[index % notes.len()];
+        let absolute_beat = (repetition * self.audio_loop.length) as f32 + note.beat;
+        (note, absolute_beat)
+    }
+
+    /// Which repetition of the loop is playing at `absolute_beat`, and how
+    /// many beats into it a voice started then should pick up. Voices only
+    /// start from the top when the loop has just rolled over from
+    /// `playing`; anything else, like an element spawning late or a voice
+    /// moving to a new device, picks up partway through so that the audio
+    /// lines up with the notes.
+    pub fn voice_start(&self, playing: Option<usize>, absolute_beat: f32) -> (usize, f32) {
+        let (iteration, beat) = self.audio_loop.position(absolute_beat);
+        let rolled_over = playing.map_or(false, |playing| playing + 1 == iteration);
+        if rolled_over {
+            (iteration, 0.)
+        } else {
+            (iteration, beat)
+        }
+    }
+
+    /// Moves past any notes at or before `absolute_beat`, returning the next
+    /// note if it hasn't been returned already. Loops without notes never
+    /// return anything.
+    pub fn advance(&mut self, absolute_beat: f32) -> Option<UpcomingNote> {
+        if self.audio_loop.beats.is_empty() || self.audio_loop.length == 0 {
+            return None;
+        }
+
+        while self.note_at(self.next).1 <= absolute_beat {
+            self.next += 1;
+        }
+
+        if self.returned == Some(self.next) {
+            return None;
+        }
+        self.returned = Some(self.next);
+
+        let (note, note_beat) = self.note_at(self.next);
+        Some(UpcomingNote {
+            note,
+            absolute_beat: note_beat,
+            beats_until: note_beat - absolute_beat,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::BEATS_PER_LOOP;
+
+    /// How finely the song is stepped through, in beats. Frames at 60 fps are
+    /// about 1/43rd of a beat at the default tempo.
+    const STEP: f32 = 1. / 64.;
+
+    /// Steps through `repetitions` repetitions of `audio_loop`, collecting
+    /// every note the cursor returns.
+    fn walk(audio_loop: &'static Loop, start: f32, repetitions: usize) -> Vec<UpcomingNote> {
+        let mut cursor = BeatCursor::new(audio_loop);
+        let end = (audio_loop.length * repetitions) as f32;
+        let mut notes = Vec::new();
+        let mut beat = start;
+        while beat < end {
+            notes.extend(cursor.advance(beat));
+            beat += STEP;
+        }
+        notes
+    }
+
+    fn looped_notes() -> impl Iterator<Item = &'static Loop> {
+        Loop::all().iter().filter(|l| !l.beats.is_empty())
+    }
+
+    #[test]
+    fn loops_without_notes_never_advance() {
+        for audio_loop in Loop::all().iter().filter(|l| l.beats.is_empty()) {
+            assert!(walk(audio_loop, 0., 2).is_empty(), "{}", audio_loop.name);
+        }
+    }
+
+    #[test]
+    fn notes_are_sorted_and_inside_their_loops() {
+        for audio_loop in looped_notes() {
+            let mut last = -1.;
+            for note in &audio_loop.beats {
+                assert!(note.beat > last, "{} isn't sorted", audio_loop.name);
+                assert!(
+                    note.beat < audio_loop.length as f32,
+                    "{} has a note past its end",
+                    audio_loop.name
+                );
+                last = note.beat;
+            }
+        }
+    }
+
+    #[test]
+    fn every_note_is_returned_once_per_repetition() {
+        for audio_loop in looped_notes() {
+            let notes = walk(audio_loop, 0., 3);
+            // The first note has already passed at beat 0, and the first note
+            // of the fourth repetition is returned as the third one ends
+            assert_eq!(
+                notes.len(),
+                audio_loop.beats.len() * 3,
+                "{}",
+                audio_loop.name
+            );
+
+            for (index, upcoming) in notes.iter().enumerate() {
+                let index = index + 1;
+                let repetition = index / audio_loop.beats.len();
+                let note = audio_loop.beats[index % audio_loop.beats.len()];
+                assert_eq!(upcoming.note, note, "{}", audio_loop.name);
+                assert_eq!(
+                    upcoming.absolute_beat,
+                    (repetition * audio_loop.length) as f32 + note.beat,
+                    "{}",
+                    audio_loop.name
+                );
+                assert!(upcoming.beats_until > 0., "{}", audio_loop.name);
+            }
+        }
+    }
+
+    #[test]
+    fn the_first_note_of_each_repetition_is_returned_before_it_wraps() {
+        for audio_loop in looped_notes() {
+            let length = audio_loop.length as f32;
+            let last_note = audio_loop.beats.last().unwrap().beat;
+            let mut cursor = BeatCursor::new(audio_loop);
+            cursor.advance(last_note - STEP);
+
+            let upcoming = cursor.advance(last_note).unwrap();
+            assert_eq!(upcoming.note, audio_loop.beats[0], "{}", audio_loop.name);
+            assert_eq!(
+                upcoming.absolute_beat,
+                length + audio_loop.beats[0].beat,
+                "{}",
+                audio_loop.name
+            );
+        }
+    }
+
+    #[test]
+    fn starting_partway_through_skips_passed_notes() {
+        for audio_loop in looped_notes() {
+            let length = audio_loop.length as f32;
+            let notes = walk(audio_loop, length * 1.5, 2);
+            assert!(
+                notes.iter().all(|n| n.absolute_beat > length * 1.5),
+                "{}",
+                audio_loop.name
+            );
+            assert_eq!(
+                notes.last().map(|n| n.absolute_beat),
+                Some(length * 2. + audio_loop.beats[0].beat),
+                "{}",
+                audio_loop.name
+            );
+        }
+    }
+
+    #[test]
+    fn jumping_ahead_returns_the_next_note() {
+        for audio_loop in looped_notes() {
+            let length = audio_loop.length as f32;
+            let mut cursor = BeatCursor::new(audio_loop);
+            cursor.advance(0.);
+
+            // A long hitch shouldn't return the notes it skipped over
+            let upcoming = cursor.advance(length * 4. + 0.01).unwrap();
+            assert!(upcoming.absolute_beat > length * 4., "{}", audio_loop.name);
+            assert!(upcoming.beats_until > 0., "{}", audio_loop.name);
+            assert_eq!(cursor.advance(length * 4. + 0.01), None);
+        }
+    }
+
+    #[test]
+    fn loops_start_on_the_first_beat() {
+        // Every pattern in the manifest starts on its first beat, so repeating
+        // them should too
+        for audio_loop in looped_notes() {
+            assert_eq!(audio_loop.beats[0].beat, 0., "{}", audio_loop.name);
+        }
+    }
+
+    #[test]
+    fn voices_started_late_line_up_with_the_cursor() {
+        for audio_loop in looped_notes().filter(|l| l.length == BEATS_PER_LOOP) {
+            let length = audio_loop.length as f32;
+            // An element spawning at beat 10 of its loop, in the first
+            // repetition or after a deferred spawn
+            for &spawn in &[10., length + 10.] {
+                let mut cursor = BeatCursor::new(audio_loop);
+                let (iteration, offset) = cursor.voice_start(None, spawn);
+                assert_eq!(offset, 10., "{}", audio_loop.name);
+
+                // The next note is as far ahead of the voice as the cursor says
+                let upcoming = cursor.advance(spawn).unwrap();
+                let note_in_loop = upcoming.absolute_beat - iteration as f32 * length;
+                assert_eq!(
+                    note_in_loop - offset,
+                    upcoming.beats_until,
+                    "{}",
+                    audio_loop.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn voices_start_from_the_top_when_the_loop_rolls_over() {
+        let audio_loop = looped_notes().next().unwrap();
+        let length = audio_loop.length as f32;
+        let cursor = BeatCursor::new(audio_loop);
+        assert_eq!(cursor.voice_start(Some(0), length + 0.02), (1, 0.));
+        // Spawning or moving devices later on still picks up mid-loop
+        assert_eq!(cursor.voice_start(None, length + 10.), (1, 10.));
+        assert_eq!(cursor.voice_start(Some(1), length + 10.), (1, 10.));
+    }
+
+    #[test]
+    fn spawns_deferred_past_a_measure_boundary_pick_up_mid_loop() {
+        for audio_loop in looped_notes() {
+            let length = audio_loop.length as f32;
+            // Waiting for an animation to free up can push the element's first
+            // beat well past the measure it was picked in
+            let spawn = length * 3. + 5.25;
+            let mut cursor = BeatCursor::new(audio_loop);
+            let (iteration, offset) = cursor.voice_start(None, spawn);
+            assert_eq!((iteration, offset), (3, 5.25), "{}", audio_loop.name);
+
+            let upcoming = cursor.advance(spawn).unwrap();
+            assert_eq!(
+                upcoming.absolute_beat - spawn,
+                upcoming.beats_until,
+                "{}",
+                audio_loop.name
+            );
+            assert!(
+                upcoming.absolute_beat - iteration as f32 * length > offset,
+                "{}",
+                audio_loop.name
+            );
+        }
+    }
+
+    #[test]
+    fn short_loops_restart_twice_per_measure() {
+        let hats = Loop::named("Drums_hh");
+        let measure = BEATS_PER_LOOP as f32;
+        let mut cursor = BeatCursor::new(hats);
+        let mut returned = Vec::new();
+        let mut playing = None;
+        let mut voices = Vec::new();
+        let mut beat = 0.;
+        while beat < measure {
+            returned.extend(cursor.advance(beat));
+            // Elements start a new voice each time the iteration changes
+            let (iteration, offset) = cursor.voice_start(playing, beat);
+            if playing != Some(iteration) {
+                voices.push((beat, offset));
+                playing = Some(iteration);
+            }
+            beat += STEP;
+        }
+
+        // The hats play through twice in the measure, and the first note of
+        // the next measure is returned as it ends
+        assert_eq!(returned.len(), hats.beats.len() * 2);
+        assert_eq!(returned[hats.beats.len() - 1].absolute_beat, 16.);
+        assert_eq!(returned[hats.beats.len() - 1].note, hats.beats[0]);
+        assert_eq!(returned.last().unwrap().absolute_beat, measure);
+        // Both voices start from the top of the loop, right as it begins
+        assert_eq!(voices, vec![(0., 0.), (16., 0.)]);
+    }
+
+    #[test]
+    fn short_loops_started_mid_measure_pick_up_mid_loop() {
+        let hats = Loop::named("Drums_hh");
+        let mut cursor = BeatCursor::new(hats);
+        // A quarter of the way through the second measure is halfway through
+        // the hats' third repetition
+        let (iteration, offset) = cursor.voice_start(None, 40.25);
+        assert_eq!((iteration, offset), (2, 8.25));
+        let upcoming = cursor.advance(40.25).unwrap();
+        assert_eq!(upcoming.absolute_beat, 40.5);
+        assert_eq!(upcoming.beats_until, 0.25);
+        assert_eq!(upcoming.absolute_beat - 32., offset + upcoming.beats_until);
+    }
+
+    #[test]
+    fn long_loops_span_several_measures() {
+        let audio_loop = Loop::repeating(64, &[0., 8.], 32);
+        let notes = walk(audio_loop, 0., 2);
+        let returned = notes.iter().map(|n| n.absolute_beat).collect::<Vec<_>>();
+        assert_eq!(returned, vec![8., 32., 40., 64., 72., 96., 104., 128.]);
+    }
+}
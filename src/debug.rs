@@ -0,0 +1,118 @@
+use crate::{
+    beats_per_second,
+    clock::{DriftStats, OUTPUT_LATENCY_SECONDS},
+    judge::QueueSnapshot,
+    seconds_per_beat,
+};
+use once_cell::sync::Lazy;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Each element's queue of beats to hit, keyed by the name of its loop and the
+/// id of its `QueueReport`.
+static QUEUES: Lazy<Mutex<BTreeMap<(&'static str, usize), QueueSnapshot>>> =
+    Lazy::new(Default::default);
+static NEXT_REPORT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns true if the debug overlay is showing.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Shows or hides the debug overlay, returning true if it's now showing.
+pub fn toggle() -> bool {
+    let enabled = !is_enabled();
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        QUEUES.lock().unwrap().clear();
+    }
+    enabled
+}
+
+/// Shows an element's queue of beats to hit on the overlay, until it's
+/// forgotten or dropped along with the element.
+pub struct QueueReport {
+    loop_name: &'static str,
+    id: usize,
+}
+
+impl QueueReport {
+    pub fn new(loop_name: &'static str) -> Self {
+        Self {
+            loop_name,
+            id: NEXT_REPORT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn report(&self, queue: QueueSnapshot) {
+        QUEUES
+            .lock()
+            .unwrap()
+            .insert((self.loop_name, self.id), queue);
+    }
+
+    /// Stops showing the queue.
+    pub fn forget(&self) {
+        QUEUES.lock().unwrap().remove(&(self.loop_name, self.id));
+    }
+}
+
+impl Drop for QueueReport {
+    fn drop(&mut self) {
+        self.forget();
+    }
+}
+
+/// What the conductor knows about the song, for the debug overlay.
+pub struct Snapshot {
+    pub absolute_beat: f32,
+    pub tempo: f32,
+    pub frame_time: Duration,
+    /// How far the conductor is from the audio, if anything is keeping the
+    /// audio clock
+    pub drift: Option<DriftStats>,
+}
+
+impl Snapshot {
+    /// The text of the debug overlay.
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "beat {:.2} at {:.1} bpm\nframe {:.1} ms\n",
+            self.absolute_beat,
+            self.tempo,
+            self.frame_time.as_secs_f32() * 1000.
+        );
+        match self.drift {
+            Some(drift) => {
+                let millis = |beats: f32| beats * seconds_per_beat(self.tempo) * 1000.;
+                let _ = writeln!(
+                    text,
+                    "drift {:+.0} ms, smoothed {:+.0} ms, largest {:+.0} ms",
+                    millis(drift.measured),
+                    millis(drift.smoothed),
+                    millis(drift.largest)
+                );
+                let _ = writeln!(
+                    text,
+                    "correcting {:+.2}%, assuming {:.0} ms output latency",
+                    drift.correction / beats_per_second(self.tempo) * 100.,
+                    OUTPUT_LATENCY_SECONDS * 1000.
+                );
+            }
+            None => text.push_str("drift unknown\n"),
+        }
+        let now = Instant::now();
+        for ((loop_name, _), queue) in QUEUES.lock().unwrap().iter() {
+            let _ = writeln!(text, "{}: {}", loop_name, queue.describe(now));
+        }
+        text
+    }
+}
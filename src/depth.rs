@@ -0,0 +1,74 @@
+use kludgine::prelude::*;
+
+/// Groups of entities, drawn from back to front in the order listed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Backdrop,
+    Elements,
+    Feedback,
+    Hud,
+}
+
+/// Where an entity is drawn. Within a layer, higher orders are drawn on top.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Depth {
+    pub layer: Layer,
+    pub order: i32,
+}
+
+impl Depth {
+    pub fn new(layer: Layer, order: i32) -> Self {
+        Self { layer, order }
+    }
+}
+
+impl From<Layer> for Depth {
+    fn from(layer: Layer) -> Self {
+        Self::new(layer, 0)
+    }
+}
+
+struct DepthEntry {
+    index: Index,
+    depth: Depth,
+    bounds: AbsoluteBounds,
+}
+
+/// Tracks the depth and bounds of a component's children so that they can be
+/// laid out, and therefore drawn, from back to front.
+#[derive(Default)]
+pub struct DepthMap {
+    entries: Vec<DepthEntry>,
+}
+
+impl DepthMap {
+    pub fn insert<I: Into<Depth>>(&mut self, index: Index, depth: I, bounds: AbsoluteBounds) {
+        self.remove(index);
+        self.entries.push(DepthEntry {
+            index,
+            depth: depth.into(),
+            bounds,
+        });
+    }
+
+    pub fn set_depth<I: Into<Depth>>(&mut self, index: Index, depth: I) {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.index == index) {
+            entry.depth = depth.into();
+        }
+    }
+
+    pub fn remove(&mut self, index: Index) {
+        self.entries.retain(|e| e.index != index);
+    }
+
+    pub fn layout(&mut self) -> KludgineResult<Box<dyn LayoutSolver>> {
+        // Stable, so entities at the same depth keep the order they were inserted in
+        self.entries.sort_by_key(|e| e.depth);
+
+        let mut layout = Layout::absolute();
+        for entry in &self.entries {
+            layout = layout.child(&entry.index, entry.bounds.clone())?;
+        }
+        layout.layout()
+    }
+}
@@ -1,153 +1,399 @@
 use crate::{
-    assets::{Animation, Loop},
+    assets::{Animation, Loop, Note, NoteKind},
+    audio::Voice,
+    cursor::BeatCursor,
+    debug,
+    judge::{
+        millis_until, BeatQueue, Judgement, Miss, Outcome, Progress, ProgressChange, ScheduledNote,
+    },
     seconds_per_beat,
+    theme::Theme,
 };
 use kludgine::prelude::*;
-use std::{
-    collections::VecDeque,
-    time::{Duration, Instant},
-};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub enum ElementCommand {
+    /// Sets the number of beats since the game started and the current tempo.
+    /// Each element tracks where it is in its own loop, since loops can be
+    /// shorter than a measure.
     SetBeat {
-        is_new_measure: bool,
-        beat: f32,
-        measure: usize,
+        absolute_beat: f32,
+        tempo: f32,
     },
     SetVolume(f32),
-}
-
-#[derive(Debug, Clone)]
-pub enum ElementMessage {
-    ImageEvent(ControlEvent),
+    /// Fades the element and its loop out over `beats` beats, after which
+    /// `ElementEvent::Retired` is sent.
+    Retire {
+        beats: f32,
+    },
+    /// Finishes a drag note after the game has checked where it was dropped.
+    FinishDrag {
+        miss: Option<Miss>,
+        window_position: Point<Points>,
+        offset_millis: Option<i64>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub enum ElementEvent {
     LoopLockedIn,
+    /// The pending element's progress towards locking in changed
+    Progress(f32),
+    /// The pending element's progress fell back to nothing
+    ProgressLost,
     Soloing(Index),
     StoppingSolo,
-    Success(Point<Points>),
-    Failure(Option<Point<Points>>),
+    /// A beat was hit. Offsets are how far from the beat the click landed, in
+    /// milliseconds: negative is early, positive is late.
+    Success {
+        location: Point<Points>,
+        offset_millis: i64,
+    },
+    Failure {
+        location: Option<Point<Points>>,
+        miss: Miss,
+        /// Missing for beats that were never clicked
+        offset_millis: Option<i64>,
+    },
+    /// The element finished fading out and can be removed.
+    Retired(Index),
+    /// A drag note was released. The game decides whether it landed on
+    /// another element.
+    DragReleased {
+        element: Index,
+        window_position: Point<Points>,
+        miss: Option<Miss>,
+        offset_millis: Option<i64>,
+    },
 }
 
+/// How far a retiring element drifts upwards as it fades out, in points.
+const RETIREMENT_DRIFT: f32 = 32.;
+
+/// An element fading out before it is removed.
 #[derive(Debug, Clone, Copy)]
-enum ElementProgress {
-    Pending(f32),
-    LockedIn,
+struct Retirement {
+    start: Instant,
+    duration: Duration,
 }
 
-impl ElementProgress {
-    pub fn percent(&self) -> f32 {
-        match self {
-            ElementProgress::Pending(value) => *value,
-            ElementProgress::LockedIn => 1.,
-        }
-    }
-
-    pub fn min_percent(&self) -> f32 {
-        match self {
-            ElementProgress::Pending(value) => value / 3. * 0.9 + 0.1,
-            ElementProgress::LockedIn => 1.,
+impl Retirement {
+    /// Returns how far along the fade out is, from 0 to 1.
+    fn progress(&self, now: Instant) -> f32 {
+        let elapsed = now
+            .checked_duration_since(self.start)
+            .unwrap_or_default()
+            .as_secs_f32();
+        let duration = self.duration.as_secs_f32();
+        if duration > 0. {
+            (elapsed / duration).min(1.)
+        } else {
+            1.
         }
     }
 }
 
 pub struct Element {
     animation: &'static Animation,
-    beats_per_loop: usize,
     tempo: f32,
     volume: f32,
     audio_loop: &'static Loop,
     image: Entity<Image>,
-    measure: Option<usize>,
-    current_beat: Option<usize>,
-    beats_to_hit: VecDeque<Instant>,
-    progress: ElementProgress,
+    approach_marker: Entity<Image>,
+    approach_beats: f32,
+    /// How much of the approach to the next beat remains, from 1 down to 0
+    approach: Option<f32>,
+    iteration: Option<usize>,
+    cursor: BeatCursor,
+    beats_to_hit: BeatQueue,
+    /// Where the sustained note being held was pressed
+    hold_position: Option<Point<Points>>,
+    progress: Progress,
     alpha_animator: RequiresInitialization<AnimationManager<ImageAlphaAnimation>>,
     frame_animator: RequiresInitialization<AnimationManager<ImageFrameAnimation>>,
-    playing_audio: Option<rodio::Sink>,
+    playing_audio: Option<Voice>,
+    retirement: Option<Retirement>,
+    retired: bool,
+    queue_report: debug::QueueReport,
 }
 
 impl Element {
     pub fn new(
-        beats_per_loop: usize,
-        tempo: f32,
         volume: f32,
+        tempo: f32,
+        approach_beats: f32,
+        hit_window_millis: i128,
         animation: &'static Animation,
         audio_loop: &'static Loop,
     ) -> Self {
         Self {
             animation,
-            beats_per_loop,
+            approach_beats,
+            approach: None,
+            approach_marker: Entity::default(),
             tempo,
             audio_loop,
-            measure: None,
-            progress: ElementProgress::Pending(0.),
-            current_beat: None,
-            beats_to_hit: VecDeque::default(),
+            iteration: None,
+            progress: Progress::default(),
+            cursor: BeatCursor::new(audio_loop),
+            beats_to_hit: BeatQueue::new(hit_window_millis),
+            hold_position: None,
             image: Entity::default(),
             alpha_animator: Default::default(),
             frame_animator: Default::default(),
             playing_audio: None,
+            retirement: None,
+            retired: false,
+            queue_report: debug::QueueReport::new(audio_loop.name),
             volume,
         }
     }
 
-    fn next_beat_start(&self, mut current_beat: f32) -> (f32, f32) {
-        let beat_start = match self.current_beat {
-            Some(index) => {
-                if let Some(beat) = self.audio_loop.beats.get(index + 1) {
-                    *beat
-                } else {
-                    current_beat -= self.beats_per_loop as f32;
-                    self.audio_loop.beats[0]
+    async fn increment_progress(&mut self, context: &mut Context, factor: f32) {
+        let had_progress = self.progress.percent() > 0.;
+        match self.progress.add(factor, self.audio_loop.beats.len()) {
+            ProgressChange::Progress(progress) => {
+                self.callback(context, ElementEvent::Progress(progress))
+                    .await;
+                if had_progress && progress <= 0. {
+                    self.callback(context, ElementEvent::ProgressLost).await;
                 }
             }
-            None => self.audio_loop.beats[0],
-        };
+            ProgressChange::LockedIn => {
+                self.callback(context, ElementEvent::LoopLockedIn).await;
+            }
+            ProgressChange::Unchanged => {}
+        }
+    }
 
-        (beat_start, current_beat)
+    /// Tells the game how a beat was judged and updates the progress to match.
+    async fn report(
+        &mut self,
+        context: &mut Context,
+        outcome: Outcome,
+        location: Option<Point<Points>>,
+    ) {
+        tracing::debug!(
+            audio_loop = self.audio_loop.name,
+            judgement = ?outcome.judgement,
+            factor = outcome.factor,
+            "judged"
+        );
+        let event = match outcome.judgement {
+            Judgement::Hit { offset_millis } => ElementEvent::Success {
+                location: location.unwrap_or_default(),
+                offset_millis,
+            },
+            Judgement::Missed {
+                miss,
+                offset_millis,
+            } => ElementEvent::Failure {
+                location,
+                miss,
+                offset_millis,
+            },
+        };
+        self.callback(context, event).await;
+        self.increment_progress(context, outcome.factor).await;
     }
 
-    async fn increment_progress(&mut self, context: &mut Context, factor: f32) {
-        if let ElementProgress::Pending(current_progress) = self.progress {
-            // Add to progress so that 2 measures of perfect hits = 1.0
+    async fn deduct_missed_beats(&mut self, context: &mut Context) {
+        if self.progress.percent() < 1. {
+            let outcomes = self.beats_to_hit.deduct_missed(Instant::now());
+            if !self.beats_to_hit.is_holding() {
+                self.hold_position = None;
+            }
+            for outcome in outcomes {
+                self.report(context, outcome, None).await;
+            }
+        }
+    }
 
-            let mut progress =
-                current_progress + 1. / (self.audio_loop.beats.len() as f32 / 2.) * factor;
+    async fn judge_press(&mut self, context: &mut Context, window_position: Point<Points>) {
+        if let Some(outcome) = self.beats_to_hit.press(Instant::now()) {
+            if self.beats_to_hit.is_holding() {
+                self.hold_position = Some(window_position);
+            }
+            self.report(context, outcome, Some(window_position)).await;
+        }
+    }
 
-            if progress < 0. {
-                progress = 0.;
-            } else if progress >= 1. {
-                self.progress = ElementProgress::LockedIn;
-                self.callback(context, ElementEvent::LoopLockedIn).await;
-                return;
+    async fn judge_release(
+        &mut self,
+        context: &mut Context,
+        window_position: Option<Point<Points>>,
+    ) {
+        if let Some(release) = self.beats_to_hit.release(Instant::now()) {
+            let press_position = self.hold_position.take().unwrap_or_default();
+            let window_position = window_position.unwrap_or(press_position);
+
+            if let NoteKind::Drag { .. } = release.kind {
+                self.callback(
+                    context,
+                    ElementEvent::DragReleased {
+                        element: context.index(),
+                        window_position,
+                        miss: release.miss,
+                        offset_millis: release.offset_millis,
+                    },
+                )
+                .await;
+            } else {
+                self.report(
+                    context,
+                    Outcome::sustain_finished(release.miss, release.offset_millis),
+                    Some(window_position),
+                )
+                .await;
             }
-            self.progress = ElementProgress::Pending(progress);
         }
     }
 
-    async fn deduct_missed_beats(&mut self, context: &mut Context) {
-        if self.progress.percent() < 1. {
-            let now = Instant::now();
+    fn animate_note(&mut self, note: &Note, hit: Instant, release: Option<Instant>) {
+        // Start at 10 ms behind when the beat will hit, so that the fade-in happens over 10ms and it
+        // peaks on the beat
+        let fade_in_start = hit.checked_sub(Duration::from_millis(10)).unwrap_or(hit);
+        self.alpha_animator.push_frame(
+            self.image
+                .animate()
+                .alpha(self.progress.min_percent(), LinearTransition),
+            fade_in_start,
+        );
 
-            if let Some(beat_instant) = self.beats_to_hit.front() {
-                let delta = instant_delta_in_millis(*beat_instant, now);
+        // Fade into the target alpha, pulsing brighter at the start of each bar
+        let peak_alpha = if self.audio_loop.meter.is_downbeat(note.beat) {
+            self.progress.percent() * 0.6 + 0.4
+        } else {
+            self.progress.percent() * 0.7 + 0.3
+        };
+        self.alpha_animator.push_frame(
+            self.image.animate().alpha(peak_alpha, LinearTransition),
+            hit,
+        );
 
-                if delta < -100 {
-                    self.increment_progress(context, -0.5).await;
-                    self.beats_to_hit.pop_front();
-                }
+        // Sustained notes stay lit until they should be released
+        let fade_out_start = match release {
+            Some(release) => {
+                self.alpha_animator.push_frame(
+                    self.image.animate().alpha(peak_alpha, LinearTransition),
+                    release,
+                );
+                release
+            }
+            None => hit,
+        };
+
+        // Fade out over 500ms
+        self.alpha_animator.push_frame(
+            self.image
+                .animate()
+                .alpha(self.progress.min_percent(), LinearTransition),
+            fade_out_start + Duration::from_millis(500),
+        );
+
+        // Execute the animation over 1/10th of a second
+        let frame_start = hit.checked_sub(Duration::from_millis(150)).unwrap_or(hit);
+        self.frame_animator.push_frame(
+            self.image.animate().frame(0., LinearTransition),
+            frame_start,
+        );
+
+        let frame_end = match (note.kind, release) {
+            (NoteKind::Hold { .. }, Some(release)) => {
+                // Hold notes stay on their last frame while held
+                self.frame_animator
+                    .push_frame(self.image.animate().frame(1., LinearTransition), hit);
+                release
             }
+            // Drag notes slowly play through their frames as they're dragged
+            (NoteKind::Drag { .. }, Some(release)) => release,
+            _ => hit + Duration::from_millis(150),
+        };
+        self.frame_animator
+            .push_frame(self.image.animate().frame(1., LinearTransition), frame_end);
+
+        self.frame_animator.push_frame(
+            self.image.animate().frame(0., LinearTransition),
+            frame_end + Duration::from_millis(1),
+        );
+    }
+
+    /// Updates how close the next beat to hit is, so that the approach marker
+    /// can start orbiting `approach_beats` before it.
+    async fn update_approach(&mut self) -> KludgineResult<()> {
+        let window = seconds_per_beat(self.tempo) * self.approach_beats;
+        let approach = if self.progress.percent() < 1. && self.retirement.is_none() && window > 0. {
+            self.beats_to_hit.front().and_then(|note| {
+                let remaining = millis_until(note.hit, Instant::now()) as f32 / 1000.;
+                if remaining >= 0. && remaining <= window {
+                    Some(remaining / window)
+                } else {
+                    None
+                }
+            })
+        } else {
+            None
+        };
+
+        if approach.is_some() != self.approach.is_some() {
+            let alpha = if approach.is_some() { 1. } else { 0. };
+            self.approach_marker
+                .send(ImageCommand::SetAlpha(alpha))
+                .await?;
+        }
+        self.approach = approach;
+        Ok(())
+    }
+
+    /// The volume to play at, taking into account fading out while retiring.
+    fn effective_volume(&self) -> f32 {
+        match &self.retirement {
+            Some(retirement) => self.volume * (1. - retirement.progress(Instant::now())),
+            None => self.volume,
         }
     }
 
     fn set_volume(&mut self, volume: f32) {
         self.volume = volume;
+        let volume = self.effective_volume();
         if let Some(playing_audio) = self.playing_audio.as_mut() {
-            playing_audio.set_volume(self.volume);
+            playing_audio.set_volume(volume);
+        }
+    }
+
+    fn retire(&mut self, beats: f32) {
+        if self.retirement.is_some() {
+            return;
+        }
+
+        tracing::debug!(audio_loop = self.audio_loop.name, beats, "retiring element");
+        let start = Instant::now();
+        // Without a usable tempo there's nothing to fade over, so retire at once
+        let seconds = seconds_per_beat(self.tempo) * beats;
+        let duration = if seconds.is_finite() && seconds > 0. {
+            Duration::from_secs_f32(seconds)
+        } else {
+            Duration::default()
+        };
+        self.retirement = Some(Retirement { start, duration });
+        self.alpha_animator.push_frame(
+            self.image.animate().alpha(0., LinearTransition),
+            start + duration,
+        );
+    }
+
+    async fn update_retirement(&mut self, context: &mut Context) {
+        if let Some(retirement) = self.retirement {
+            self.set_volume(self.volume);
+
+            if !self.retired && retirement.progress(Instant::now()) >= 1. {
+                self.retired = true;
+                self.queue_report.forget();
+                tracing::debug!(audio_loop = self.audio_loop.name, "element retired");
+                self.callback(context, ElementEvent::Retired(context.index()))
+                    .await;
+            }
         }
     }
 }
@@ -157,7 +403,15 @@ impl Component for Element {
     async fn initialize(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
         self.image = self
             .new_entity(context, Image::new(self.animation.sprite.clone()))
-            .callback(ElementMessage::ImageEvent)
+            .insert()
+            .await?;
+
+        self.approach_marker = self
+            .new_entity(
+                context,
+                Image::new(Theme::current().particle.clone())
+                    .options(ImageOptions::default().alpha(0.0)),
+            )
             .insert()
             .await?;
 
@@ -176,8 +430,58 @@ impl Component for Element {
         Ok(())
     }
 
+    async fn layout(
+        &mut self,
+        _context: &mut StyledContext,
+    ) -> KludgineResult<Box<dyn LayoutSolver>> {
+        // Retiring elements drift upwards as they fade out
+        let drift = self
+            .retirement
+            .map(|retirement| retirement.progress(Instant::now()) * RETIREMENT_DRIFT)
+            .unwrap_or_default();
+        let layout = Layout::absolute().child(
+            &self.image,
+            AbsoluteBounds {
+                left: Dimension::from_points(0.),
+                top: Dimension::from_points(-drift),
+                right: Dimension::from_points(0.),
+                bottom: Dimension::from_points(drift),
+                ..Default::default()
+            },
+        )?;
+
+        let element_size = self.animation.sprite.size().await;
+        let marker_size = Theme::current().particle.size().await;
+        match (self.approach, element_size, marker_size) {
+            (Some(approach), Some(element_size), Some(marker_size)) => {
+                // Spiral in towards the element, completing an orbit exactly on the beat
+                let radius = element_size.width.max(element_size.height) as f32 / 2.;
+                let distance = radius * (0.6 + 0.6 * approach);
+                let angle =
+                    -std::f32::consts::FRAC_PI_2 + std::f32::consts::PI * 2. * (1. - approach);
+                let left = element_size.width as f32 / 2. + distance * angle.cos()
+                    - marker_size.width as f32 / 2.;
+                let top = element_size.height as f32 / 2. + distance * angle.sin()
+                    - marker_size.height as f32 / 2.;
+                layout
+                    .child(
+                        &self.approach_marker,
+                        AbsoluteBounds {
+                            left: Dimension::from_points(left),
+                            top: Dimension::from_points(top),
+                            ..Default::default()
+                        },
+                    )?
+                    .layout()
+            }
+            _ => layout.layout(),
+        }
+    }
+
     async fn update(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
         self.deduct_missed_beats(context).await;
+        self.update_approach().await?;
+        self.update_retirement(context).await;
         self.alpha_animator.update(context).await;
         self.frame_animator.update(context).await;
         Ok(())
@@ -193,160 +497,94 @@ impl Component for Element {
         self.callback(context, ElementEvent::StoppingSolo).await;
         Ok(())
     }
-}
 
-fn instant_delta_in_millis(a: Instant, b: Instant) -> i128 {
-    if let Some(delta) = a.checked_duration_since(b) {
-        delta.as_millis() as i128
-    } else {
-        -(b.checked_duration_since(a).unwrap().as_millis() as i128)
+    async fn mouse_down(
+        &mut self,
+        context: &mut Context,
+        window_position: Point<Points>,
+        _button: MouseButton,
+    ) -> KludgineResult<EventStatus> {
+        self.judge_press(context, window_position).await;
+        Ok(EventStatus::Processed)
+    }
+
+    async fn mouse_up(
+        &mut self,
+        context: &mut Context,
+        window_position: Option<Point<Points>>,
+        _button: MouseButton,
+    ) -> KludgineResult<()> {
+        self.judge_release(context, window_position).await;
+        Ok(())
     }
 }
 
 #[async_trait]
 impl InteractiveComponent for Element {
-    type Message = ElementMessage;
+    type Message = ();
     type Input = ElementCommand;
     type Output = ElementEvent;
 
     async fn receive_input(
         &mut self,
-        _context: &mut Context,
+        context: &mut Context,
         command: Self::Input,
     ) -> KludgineResult<()> {
         match command {
             ElementCommand::SetBeat {
-                is_new_measure,
-                beat,
-                measure,
+                absolute_beat,
+                tempo,
             } => {
-                if self.playing_audio.is_none() || is_new_measure {
-                    if let Some(device) = rodio::default_output_device() {
-                        let sink = rodio::Sink::new(&device);
-                        sink.append(self.audio_loop.source.clone());
-                        sink.set_volume(self.volume);
-                        self.playing_audio = Some(sink);
-                    }
-
-                    self.current_beat = None;
-                    self.measure = Some(measure);
+                self.tempo = tempo;
+                let (iteration, beat) = self.cursor.voice_start(self.iteration, absolute_beat);
+                let stale = self.playing_audio.as_ref().map_or(false, Voice::is_stale);
+                if self.playing_audio.is_none() || self.iteration != Some(iteration) || stale {
+                    self.playing_audio =
+                        Voice::play(self.audio_loop, beat, self.effective_volume());
+                    self.iteration = Some(iteration);
                 }
 
-                let (next_beat_start, adjusted_beat) = self.next_beat_start(beat);
+                if let Some(upcoming) = self.cursor.advance(absolute_beat) {
+                    let remaining_seconds = seconds_per_beat(self.tempo) * upcoming.beats_until;
+                    if self.retirement.is_none() {
+                        let next_beat_instant =
+                            Instant::now() + Duration::from_secs_f32(remaining_seconds);
 
-                if adjusted_beat > next_beat_start {
-                    let mut new_beat_index = self.current_beat.map(|beat| beat + 1).unwrap_or(0);
-                    if new_beat_index > self.audio_loop.beats.len() {
-                        new_beat_index = 0;
-                    }
-                    self.current_beat = Some(new_beat_index);
-
-                    let (next_beat, adjusted_beat) = self.next_beat_start(beat);
-                    let remaining_beats = next_beat - adjusted_beat;
-                    let remaining_seconds = seconds_per_beat(self.tempo) * remaining_beats;
-                    if remaining_seconds > 0. {
-                        let next_beat_instant = Instant::now()
-                            .checked_add(Duration::from_secs_f32(remaining_seconds))
-                            .unwrap();
-
-                        self.beats_to_hit.push_back(next_beat_instant);
-
-                        // Start at 10 ms behind when the beat will hit, so that the fade-in happens over 10ms and it
-                        // peaks on the beat
-                        let next_beat_start = next_beat_instant
-                            .checked_sub(Duration::from_millis(10))
-                            .unwrap();
-                        self.alpha_animator.push_frame(
-                            self.image
-                                .animate()
-                                .alpha(self.progress.min_percent(), LinearTransition),
-                            next_beat_start,
-                        );
-
-                        // Fade into the target alpha
-                        self.alpha_animator.push_frame(
-                            self.image
-                                .animate()
-                                .alpha(self.progress.percent() * 0.7 + 0.3, LinearTransition),
-                            next_beat_instant,
-                        );
-
-                        // Fade out over 500ms
-                        self.alpha_animator.push_frame(
-                            self.image
-                                .animate()
-                                .alpha(self.progress.min_percent(), LinearTransition),
+                        let release = upcoming.note.length().map(|length| {
                             next_beat_instant
-                                .checked_add(Duration::from_millis(500))
-                                .unwrap(),
-                        );
-
-                        // Execute the animation over 1/10th of a second
-                        let frame_start = next_beat_instant
-                            .checked_sub(Duration::from_millis(150))
-                            .unwrap();
-                        self.frame_animator.push_frame(
-                            self.image.animate().frame(0., LinearTransition),
-                            frame_start,
-                        );
-
-                        let frame_end = next_beat_instant
-                            .checked_add(Duration::from_millis(150))
-                            .unwrap();
-                        self.frame_animator.push_frame(
-                            self.image.animate().frame(1., LinearTransition),
-                            frame_end,
-                        );
-
-                        self.frame_animator.push_frame(
-                            self.image.animate().frame(0., LinearTransition),
-                            frame_end.checked_add(Duration::from_millis(1)).unwrap(),
-                        );
+                                + Duration::from_secs_f32(seconds_per_beat(self.tempo) * length)
+                        });
+                        self.beats_to_hit.push(ScheduledNote {
+                            hit: next_beat_instant,
+                            release,
+                            kind: upcoming.note.kind,
+                        });
+
+                        self.animate_note(&upcoming.note, next_beat_instant, release);
                     }
                 }
+
+                if debug::is_enabled() && !self.retired {
+                    self.queue_report.report(self.beats_to_hit.snapshot());
+                }
             }
             ElementCommand::SetVolume(volume) => {
                 self.set_volume(volume);
             }
-        }
-        Ok(())
-    }
-
-    async fn receive_message(
-        &mut self,
-        context: &mut Context,
-        message: Self::Message,
-    ) -> KludgineResult<()> {
-        match message {
-            ElementMessage::ImageEvent(ControlEvent::Clicked {
-                window_position, ..
-            }) => {
-                let now = Instant::now();
-
-                if let Some(beat_instant) = self.beats_to_hit.pop_front() {
-                    let delta = instant_delta_in_millis(beat_instant, now);
-                    match delta {
-                        i128::MIN..=-151 | 151..=200 => {
-                            // Missed the beat entirely or clicked a bit too soon
-                            self.callback(context, ElementEvent::Failure(Some(window_position)))
-                                .await;
-                            self.increment_progress(context, -0.5).await;
-                        }
-                        -150..=150 => {
-                            self.callback(context, ElementEvent::Success(window_position))
-                                .await;
-                            self.increment_progress(context, 1.).await;
-                        }
-                        201..=i128::MAX => {
-                            // Far in the future, the click should count against the player
-                            // but the beat should still be clickable.
-                            self.callback(context, ElementEvent::Failure(Some(window_position)))
-                                .await;
-                            self.increment_progress(context, -0.5).await;
-                            self.beats_to_hit.push_front(beat_instant);
-                        }
-                    }
-                }
+            ElementCommand::Retire { beats } => {
+                self.retire(beats);
+            }
+            ElementCommand::FinishDrag {
+                miss,
+                window_position,
+                offset_millis,
+            } => {
+                self.report(
+                    context,
+                    Outcome::sustain_finished(miss, offset_millis),
+                    Some(window_position),
+                )
+                .await;
             }
         }
         Ok(())
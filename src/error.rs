@@ -0,0 +1,76 @@
+use kludgine::prelude::*;
+
+/// Shown instead of the game when something it needs couldn't be loaded.
+pub struct ErrorScreen {
+    message: String,
+    heading: Entity<Label>,
+    details: Entity<Label>,
+}
+
+impl ErrorScreen {
+    pub fn new(error: &anyhow::Error) -> Self {
+        Self {
+            message: format!("{:#}", error),
+            heading: Default::default(),
+            details: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Component for ErrorScreen {
+    async fn initialize(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
+        self.heading = self
+            .new_entity(context, Label::new("Chillscapes couldn't start"))
+            .style(Style {
+                font_size: Some(32.),
+                ..Default::default()
+            })
+            .insert()
+            .await?;
+
+        self.details = self
+            .new_entity(context, Label::new(&self.message))
+            .style(Style {
+                color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+                ..Default::default()
+            })
+            .insert()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn layout(
+        &mut self,
+        context: &mut StyledContext,
+    ) -> KludgineResult<Box<dyn LayoutSolver>> {
+        let window_size = context.scene().size().await.to_f32();
+
+        Layout::absolute()
+            .child(
+                &self.heading,
+                AbsoluteBounds {
+                    top: Dimension::from_points(window_size.height / 3.),
+                    ..Default::default()
+                },
+            )?
+            .child(
+                &self.details,
+                AbsoluteBounds {
+                    top: Dimension::from_points(window_size.height / 3. + 64.),
+                    left: Dimension::from_points(32.),
+                    right: Dimension::from_points(32.),
+                    ..Default::default()
+                },
+            )?
+            .layout()
+    }
+}
+
+#[async_trait]
+impl InteractiveComponent for ErrorScreen {
+    type Message = ();
+    type Input = ();
+    type Output = ();
+}
@@ -0,0 +1,206 @@
+use crate::{clicks::HitMarker, palette};
+use kludgine::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Animations played over an element when it locks in or loses all of its
+/// progress.
+pub struct Feedback {
+    success: Sprite,
+    fail: Sprite,
+    /// How long each animation takes to play through once, from its frames
+    success_length: Duration,
+    fail_length: Duration,
+    sprite_size: Size,
+    scheduled: Vec<Scheduled>,
+    playing: Vec<Playing>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum FeedbackAnimation {
+    Success,
+    Fail,
+}
+
+#[derive(Clone, Debug)]
+pub enum FeedbackCommand {
+    /// Plays an animation centered on `location`, starting after `delay` so
+    /// that it can land on a beat.
+    Play {
+        animation: FeedbackAnimation,
+        location: Point<Points>,
+        delay: Duration,
+    },
+}
+
+struct Scheduled {
+    animation: FeedbackAnimation,
+    location: (f32, f32),
+    start: Instant,
+}
+
+/// What's shown for a playing animation: the theme's sprite, or a shape in
+/// the palette's colors in visual modes that ask for them.
+enum Visual {
+    Sprite(Entity<Image>),
+    Shape(Entity<HitMarker>),
+}
+
+struct Playing {
+    visual: Visual,
+    location: (f32, f32),
+    started: Instant,
+    length: Duration,
+}
+
+impl Playing {
+    fn is_finished(&self, now: Instant) -> bool {
+        now.checked_duration_since(self.started).unwrap_or_default() >= self.length
+    }
+}
+
+/// How long `sprite` takes to play through its untagged animation once, going
+/// by the timing of each frame.
+pub async fn play_length(sprite: &Sprite) -> Duration {
+    let animations = sprite.animations().await;
+    animations
+        .animation_for(&None)
+        .map(|animation| {
+            animation
+                .frames
+                .iter()
+                .filter_map(|frame| frame.duration)
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+impl Feedback {
+    pub fn new(success: Sprite, fail: Sprite) -> Self {
+        Self {
+            success,
+            fail,
+            success_length: Duration::default(),
+            fail_length: Duration::default(),
+            sprite_size: Size::default(),
+            scheduled: Vec::default(),
+            playing: Vec::default(),
+        }
+    }
+
+    fn sprite_for(&self, animation: FeedbackAnimation) -> &Sprite {
+        match animation {
+            FeedbackAnimation::Success => &self.success,
+            FeedbackAnimation::Fail => &self.fail,
+        }
+    }
+
+    fn length_of(&self, animation: FeedbackAnimation) -> Duration {
+        match animation {
+            FeedbackAnimation::Success => self.success_length,
+            FeedbackAnimation::Fail => self.fail_length,
+        }
+    }
+}
+
+#[async_trait]
+impl Component for Feedback {
+    async fn initialize(&mut self, _context: &mut SceneContext) -> KludgineResult<()> {
+        if let Some(size) = self.success.size().await {
+            self.sprite_size = Size::new(size.width as f32, size.height as f32);
+        }
+        self.success_length = play_length(&self.success).await;
+        self.fail_length = play_length(&self.fail).await;
+        Ok(())
+    }
+
+    async fn layout(
+        &mut self,
+        _context: &mut StyledContext,
+    ) -> KludgineResult<Box<dyn LayoutSolver>> {
+        let mut layout = Layout::absolute();
+        for playing in &self.playing {
+            let bounds = AbsoluteBounds {
+                left: Dimension::from_points(playing.location.0 - self.sprite_size.width / 2.),
+                top: Dimension::from_points(playing.location.1 - self.sprite_size.height / 2.),
+                width: Dimension::from_points(self.sprite_size.width),
+                height: Dimension::from_points(self.sprite_size.height),
+                ..Default::default()
+            };
+            layout = match &playing.visual {
+                Visual::Sprite(image) => layout.child(image, bounds)?,
+                Visual::Shape(marker) => layout.child(marker, bounds)?,
+            };
+        }
+        layout.layout()
+    }
+
+    async fn update(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
+        let now = Instant::now();
+
+        let (starting, waiting) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition::<Vec<_>, _>(|scheduled| scheduled.start <= now);
+        self.scheduled = waiting;
+        for scheduled in starting {
+            let visual = if palette::current().shapes {
+                let hit = matches!(scheduled.animation, FeedbackAnimation::Success);
+                Visual::Shape(
+                    self.new_entity(context, HitMarker::new(hit))
+                        .insert()
+                        .await?,
+                )
+            } else {
+                let sprite = self.sprite_for(scheduled.animation).clone();
+                Visual::Sprite(
+                    self.new_entity(context, Image::new(sprite))
+                        .insert()
+                        .await?,
+                )
+            };
+            self.playing.push(Playing {
+                visual,
+                location: scheduled.location,
+                started: now,
+                length: self.length_of(scheduled.animation),
+            });
+        }
+
+        for playing in self.playing.iter().filter(|p| p.is_finished(now)) {
+            match &playing.visual {
+                Visual::Sprite(image) => context.remove(image).await,
+                Visual::Shape(marker) => context.remove(marker).await,
+            }
+        }
+        self.playing.retain(|p| !p.is_finished(now));
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InteractiveComponent for Feedback {
+    type Message = ();
+    type Input = FeedbackCommand;
+    type Output = ();
+
+    async fn receive_input(
+        &mut self,
+        _context: &mut Context,
+        command: Self::Input,
+    ) -> KludgineResult<()> {
+        match command {
+            FeedbackCommand::Play {
+                animation,
+                location,
+                delay,
+            } => {
+                self.scheduled.push(Scheduled {
+                    animation,
+                    location: (location.x.to_f32(), location.y.to_f32()),
+                    start: Instant::now() + delay,
+                });
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,24 @@
 use crate::{
     assets::{Animation, Loop, LoopKind},
+    audio::Voice,
+    depth::{Depth, DepthMap, Layer},
     element::{Element, ElementCommand, ElementEvent},
+    feedback::{Feedback, FeedbackAnimation, FeedbackCommand},
+    hud::{Hud, HudCommand},
+    judge::{judge_drop, DropTarget},
+    launch,
+    metronome::{Metronome, MetronomeMode},
+    particles::{ParticleCommand, Particles},
+    random, save, seconds_per_beat, settings,
+    theme::Theme,
+    timing::TimingHistory,
+    tutorial::Tutorial,
     SceneState,
     clicks::{Clicks, ClickCommand},
 };
 use kludgine::prelude::*;
 use rand::prelude::*;
+use std::time::{Duration, Instant};
 
 struct SpawnedElement {
     element: Entity<Element>,
@@ -17,54 +30,160 @@ struct SpawnedElement {
 
 pub struct Game {
     scene_state: KludgineHandle<SceneState>,
+    pads: &'static Loop,
     help_text: Entity<Label>,
+    tutorial: Tutorial,
+    metronome: Metronome,
+    metronome_toggle: Entity<Label>,
+    /// The measure after which the current hint goes back to the tutorial's help
+    hint_expires: Option<usize>,
     clicks: Entity<Clicks>,
+    particles: Entity<Particles>,
+    feedback: Entity<Feedback>,
+    hud: Option<Entity<Hud>>,
+    score: u64,
+    /// The best score from previous games with this theme and difficulty
+    best_score: Option<u64>,
+    /// When play time was last added to the save data
+    last_recorded: Instant,
+    /// When the save file was last written during play
+    last_saved: Option<Instant>,
+    timing: TimingHistory,
+    last_reported_beat: Option<usize>,
     elements: Vec<SpawnedElement>,
     pending_element: Option<Entity<Element>>,
-    lead: Option<rodio::Sink>,
+    lead: Option<Voice>,
+    lead_loop: Option<&'static Loop>,
     last_spawned_element_measure: Option<usize>,
     next_loop_to_spawn: Option<&'static Loop>,
+    active_kinds: Vec<LoopKind>,
+    depths: DepthMap,
+    spawned_count: i32,
     volume: f32,
 }
 
 const MAX_VOLUME: f32 = 0.7;
 const QUIET_VOLUME: f32 = 0.3;
+/// Every this many measures a new section starts, where the tempo can drift a
+/// little to keep things from feeling mechanical.
+const MEASURES_PER_SECTION: usize = 4;
+/// How many beats it takes to ramp to a new section's tempo.
+const TEMPO_RAMP_BEATS: f32 = 4.;
+/// How many beats before each beat the approach marker starts orbiting.
+const APPROACH_BEATS: f32 = 1.;
+/// How many beats a retired element takes to fade out before it's removed.
+const RETIREMENT_BEATS: f32 = 8.;
+/// How many measures a hint stays up for.
+const HINT_MEASURES: usize = 2;
+/// Points for each beat hit on time.
+const HIT_SCORE: u64 = 10;
+/// Points for locking in an element.
+const LOCK_IN_SCORE: u64 = 100;
+/// The least time between writing the save file during play.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+/// How many random spots to try before putting a spawn off until later.
+const MAX_SPAWN_ATTEMPTS: usize = 32;
 
 impl Game {
-    pub fn new(scene_state: KludgineHandle<SceneState>) -> Self {
+    pub fn new(scene_state: KludgineHandle<SceneState>, pads: &'static Loop) -> Self {
         Self {
             scene_state,
+            pads,
             elements: Vec::default(),
             pending_element: None,
             lead: None,
+            lead_loop: None,
             last_spawned_element_measure: None,
             next_loop_to_spawn: None,
+            active_kinds: Vec::default(),
+            depths: DepthMap::default(),
+            spawned_count: 0,
             volume: MAX_VOLUME,
             help_text: Default::default(),
+            tutorial: if launch::config().zen {
+                Tutorial::skipped()
+            } else {
+                Tutorial::default()
+            },
+            metronome: Metronome::new(MAX_VOLUME),
+            metronome_toggle: Default::default(),
+            hint_expires: None,
             clicks: Default::default(),
+            particles: Default::default(),
+            feedback: Default::default(),
+            hud: None,
+            score: 0,
+            best_score: save::current()
+                .profile_mut()
+                .best_score(&launch::config().theme, launch::config().difficulty.name()),
+            last_recorded: Instant::now(),
+            last_saved: None,
+            timing: TimingHistory::default(),
+            last_reported_beat: None,
         }
     }
 
+    /// The loops that will keep playing, including the pads.
+    fn playing_loops(&self) -> impl Iterator<Item = &'static Loop> + '_ {
+        std::iter::once(self.pads).chain(
+            self.elements
+                .iter()
+                .filter(|el| !el.being_destroyed)
+                .map(|el| el.audio_loop),
+        )
+    }
+
     fn random_available_loop(&self) -> Option<&'static Loop> {
-        let mut rng = thread_rng();
-        Loop::all()
+        let mut rng = random::rng();
+        // The lead plays alongside the elements, so new loops have to fit with it too
+        let playing = self
+            .playing_loops()
+            .chain(self.lead_loop)
+            .collect::<Vec<_>>();
+        Loop::spawnable(&playing).choose(&mut rng)
+    }
+
+    /// What `window_position` is over, for a drag started on `dragged`.
+    fn drop_target(&self, window_position: Point<Points>, dragged: Index) -> DropTarget {
+        let x = window_position.x.to_f32();
+        let y = window_position.y.to_f32();
+        let under = self.elements.iter().filter(|se| {
+            !se.being_destroyed
+                && x >= se.location.origin.x
+                && x <= se.location.origin.x + se.location.size.width
+                && y >= se.location.origin.y
+                && y <= se.location.origin.y + se.location.size.height
+        });
+        let mut target = DropTarget::Nowhere;
+        for se in under {
+            if se.element.index() != dragged {
+                return DropTarget::OtherElement;
+            }
+            target = DropTarget::Itself;
+        }
+        target
+    }
+
+    /// Returns true if there are elements on screen other than `except`.
+    fn has_other_elements(&self, except: Index) -> bool {
+        self.elements
             .iter()
-            .filter(|l| {
-                !l.beats.is_empty()
-                    && !self
-                        .elements
-                        .iter()
-                        .any(|el| !el.being_destroyed && el.audio_loop.kind == l.kind)
-            })
-            .choose(&mut rng)
+            .any(|se| !se.being_destroyed && se.element.index() != except)
     }
 
-    fn find_spawn_location(&self, scene_size: Size, frame_size: Size<u32>) -> Rect {
-        let mut rng = thread_rng();
+    /// Looks for a free spot for a new element, returning None if the scene
+    /// is too small or too crowded for one right now.
+    fn find_spawn_location(&self, scene_size: Size, frame_size: Size<u32>) -> Option<Rect> {
+        let max_x = scene_size.width - frame_size.width as f32 - 64.;
+        let max_y = scene_size.height - frame_size.height as f32 - 64.;
+        if max_x <= 32. || max_y <= 32. {
+            return None;
+        }
 
-        loop {
-            let x = rng.gen_range(32., scene_size.width - frame_size.width as f32 - 64.);
-            let y = rng.gen_range(32., scene_size.height - frame_size.height as f32 - 64.);
+        let mut rng = random::rng();
+        for _ in 0..MAX_SPAWN_ATTEMPTS {
+            let x = rng.gen_range(32., max_x);
+            let y = rng.gen_range(32., max_y);
 
             let rect = Rect::sized(
                 Point::new(x, y),
@@ -76,22 +195,22 @@ impl Game {
                 .iter()
                 .any(|se| !se.being_destroyed && se.location.intersects_with(&rect))
             {
-                return rect;
+                return Some(rect);
             }
         }
+        None
     }
 
     fn pick_next_spawn(&mut self) {
-        self.next_loop_to_spawn = Some({
-            if let Some(audio_loop) = self.random_available_loop() {
-                audio_loop
-            } else {
-                let oldest_element = self.elements.get_mut(0).unwrap();
-                oldest_element.being_destroyed = true;
-
-                self.random_available_loop().unwrap()
-            }
-        });
+        let mut next_loop = self.random_available_loop();
+        // Retire the oldest elements until there's room for something new
+        let mut oldest = 0;
+        while next_loop.is_none() && oldest < self.elements.len() {
+            self.elements[oldest].being_destroyed = true;
+            oldest += 1;
+            next_loop = self.random_available_loop();
+        }
+        self.next_loop_to_spawn = next_loop;
     }
 
     async fn spawn_new_element(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
@@ -99,85 +218,293 @@ impl Game {
         let scene_size = context.scene().size().await.to_f32();
         if scene_size.area() > 0. {
             if let Some(audio_loop) = self.next_loop_to_spawn.take() {
+                let animation = {
+                    let mut rng = random::rng();
+                    Animation::all()
+                        .iter()
+                        .filter(|a| {
+                            !self
+                                .elements
+                                .iter()
+                                .any(|el| !el.being_destroyed && el.animation.id == a.id)
+                        })
+                        .choose(&mut rng)
+                };
+                let (animation, frame_size) = match animation {
+                    Some(animation) => match animation.sprite.size().await {
+                        Some(frame_size) => (animation, frame_size),
+                        None => return Ok(()),
+                    },
+                    // Every animation is in use, so wait for an element to
+                    // retire. The element picks its loop up partway through
+                    // when it does spawn, however many measures later.
+                    None => {
+                        self.next_loop_to_spawn = Some(audio_loop);
+                        return Ok(());
+                    }
+                };
+
+                let location = match self.find_spawn_location(scene_size, frame_size) {
+                    Some(location) => location,
+                    // No room right now, so try again on a later update
+                    None => {
+                        self.next_loop_to_spawn = Some(audio_loop);
+                        return Ok(());
+                    }
+                };
+
+                let element = self
+                    .new_entity(
+                        context,
+                        Element::new(
+                            self.volume,
+                            scene_state.tempo,
+                            APPROACH_BEATS,
+                            launch::config().difficulty.hit_window_millis(),
+                            animation,
+                            audio_loop,
+                        ),
+                    )
+                    .callback(GameMessage::ElementEvent)
+                    .insert()
+                    .await?;
+
+                // Newer elements are drawn on top of older ones
+                self.spawned_count += 1;
+                self.depths.insert(
+                    element.index(),
+                    Depth::new(Layer::Elements, self.spawned_count),
+                    AbsoluteBounds {
+                        left: Dimension::from_points(location.origin.x),
+                        top: Dimension::from_points(location.origin.y),
+                        width: Dimension::from_points(location.size.width),
+                        height: Dimension::from_points(location.size.height),
+                        ..Default::default()
+                    },
+                );
+                tracing::info!(
+                    audio_loop = audio_loop.name,
+                    animation = animation.id,
+                    measure = scene_state.measure,
+                    "spawned element"
+                );
+
+                self.elements.push(SpawnedElement {
+                    element: element.clone(),
+                    audio_loop,
+                    animation,
+                    location,
+                    being_destroyed: false,
+                });
+
+                self.pending_element = Some(element);
+                self.send_to_hud(HudCommand::SetProgress(Some(0.))).await?;
+                self.last_spawned_element_measure = Some(scene_state.measure);
+            }
+        }
 
+        Ok(())
+    }
 
-            let animation = {
-                let animations = Animation::all().await;
-                let mut rng = thread_rng();
-                animations
+    async fn generate_leads(&mut self) {
+        let scene_state = self.scene_state.read().await;
+        if self.last_spawned_element_measure.unwrap_or_default() != scene_state.measure {
+            let mut rng = random::rng();
+            // Don't always play leads
+            if rng.gen_bool(0.66) {
+                let lead_loop = Loop::all()
                     .iter()
-                    .filter(|a| {
-                        !self
-                            .elements
-                            .iter()
-                            .any(|el| !el.being_destroyed && el.animation.id == a.id)
-                    })
-                    .choose(&mut rng)
-                    .unwrap()
-            };
+                    .filter(|l| l.kind == LoopKind::Leads && l.fits_with(self.playing_loops()))
+                    .choose(&mut rng);
 
-            let frame_size = animation.sprite.size().await.unwrap();
+                self.lead = lead_loop.and_then(|lead_loop| Voice::play(lead_loop, 0., self.volume));
+                self.lead_loop = lead_loop;
+            } else {
+                self.lead = None;
+                self.lead_loop = None;
+            }
+        }
+    }
+
+    /// Ramps towards a new tempo at the start of each section, if the tempo
+    /// was asked to drift.
+    async fn start_section(&mut self) {
+        let max_drift = launch::config().tempo_drift;
+        if max_drift <= 0. {
+            return;
+        }
+
+        let mut scene_state = self.scene_state.write().await;
+        if scene_state.measure > 0 && scene_state.measure % MEASURES_PER_SECTION == 0 {
+            let drift = random::rng().gen_range(-max_drift, max_drift);
+            let tempo = scene_state.base_tempo + drift;
+            scene_state.ramp_tempo(tempo, TEMPO_RAMP_BEATS);
+        }
+    }
 
-            let location = self.find_spawn_location(scene_size, frame_size);
+    /// Lets the rest of the scene know which kinds of loops are playing, when
+    /// that changes.
+    async fn report_active_kinds(&mut self, context: &mut Context) -> KludgineResult<()> {
+        let mut kinds = self
+            .playing_loops()
+            .map(|l| l.kind.clone())
+            .collect::<Vec<_>>();
+        if self.lead_loop.is_some() {
+            kinds.push(LoopKind::Leads);
+        }
 
-            let element = self
-                .new_entity(
-                    context,
-                    Element::new(
-                        scene_state.beats_per_loop,
-                        scene_state.tempo,
-                        self.volume,
-                        animation,
-                        audio_loop,
-                    ),
+        if kinds != self.active_kinds {
+            self.active_kinds = kinds.clone();
+            self.send_to_hud(HudCommand::SetActiveKinds(kinds.clone()))
+                .await?;
+            self.callback(context, GameEvent::ActiveKindsChanged(kinds))
+                .await;
+        }
+        Ok(())
+    }
+
+    /// The center of an element on screen.
+    fn center_of(&self, element: Index) -> Option<Point<Points>> {
+        self.elements
+            .iter()
+            .find(|se| se.element.index() == element)
+            .map(|spawned| {
+                let location = &spawned.location;
+                Point::new(
+                    Points::from_f32(location.origin.x + location.size.width / 2.),
+                    Points::from_f32(location.origin.y + location.size.height / 2.),
                 )
-                .bounds(AbsoluteBounds {
-                    left: Dimension::from_points(location.origin.x),
-                    top: Dimension::from_points(location.origin.y),
-                    width: Dimension::from_points(location.size.width),
-                    height: Dimension::from_points(location.size.height),
-                    ..Default::default()
+            })
+    }
+
+    /// Plays a feedback animation over `element`, starting on the next beat.
+    async fn play_feedback(
+        &self,
+        element: Index,
+        animation: FeedbackAnimation,
+    ) -> KludgineResult<()> {
+        if let Some(location) = self.center_of(element) {
+            let delay = {
+                let scene_state = self.scene_state.read().await;
+                let beats = scene_state.absolute_beat.ceil() - scene_state.absolute_beat;
+                Duration::from_secs_f32(beats * seconds_per_beat(scene_state.tempo))
+            };
+            self.feedback
+                .send(FeedbackCommand::Play {
+                    animation,
+                    location,
+                    delay,
                 })
-                .callback(GameMessage::ElementEvent)
-                .insert()
                 .await?;
+        }
+        Ok(())
+    }
 
-            self.elements.push(SpawnedElement {
-                element: element.clone(),
-                audio_loop,
-                animation,
-                location,
-                being_destroyed: false,
-            });
+    async fn set_help_text(&self, text: &str) -> KludgineResult<()> {
+        self.help_text
+            .send(LabelCommand::SetValue(text.to_string()))
+            .await
+    }
 
-            self.pending_element = Some(element);
-            self.last_spawned_element_measure = Some(scene_state.measure);
+    async fn show_hint(&mut self, hint: &str) -> KludgineResult<()> {
+        let measure = self.scene_state.read().await.measure;
+        self.hint_expires = Some(measure + HINT_MEASURES);
+        self.set_help_text(hint).await
+    }
+
+    /// Goes back to the tutorial's help once a hint has been up long enough.
+    async fn expire_hint(&mut self, measure: usize) -> KludgineResult<()> {
+        if let Some(hint_expires) = self.hint_expires {
+            if measure >= hint_expires {
+                self.hint_expires = None;
+                self.set_help_text(self.tutorial.help_text().unwrap_or_default())
+                    .await?;
+            }
         }
+        Ok(())
     }
 
+    async fn send_to_hud(&self, command: HudCommand) -> KludgineResult<()> {
+        if let Some(hud) = &self.hud {
+            hud.send(command).await?;
+        }
         Ok(())
     }
 
-    async fn generate_leads(&mut self) {
-        let scene_state = self.scene_state.read().await;
-        if self.last_spawned_element_measure.unwrap_or_default() != scene_state.measure {
-            let mut rng = thread_rng();
-            // Don't always play leads
-            if rng.gen_bool(0.66) {
-                let lead_loop = Loop::all()
-                    .iter()
-                    .filter(|l| l.kind == LoopKind::Leads)
-                    .choose(&mut rng)
-                    .unwrap();
-
-                if let Some(device) = rodio::default_output_device() {
-                    let sink = rodio::Sink::new(&device);
-                    sink.append(lead_loop.source.clone());
-                    sink.set_volume(self.volume);
-                    self.lead = Some(sink);
-                }
-            } else {
-                self.lead = None;
+    async fn record_offset(&mut self, offset_millis: i64) -> KludgineResult<()> {
+        self.timing.record(offset_millis);
+        self.send_to_hud(HudCommand::SetTiming(self.timing.clone()))
+            .await
+    }
+
+    async fn add_score(&mut self, points: u64) -> KludgineResult<()> {
+        self.score += points;
+        self.send_to_hud(HudCommand::SetScore {
+            score: self.score,
+            best: self.best_score,
+        })
+        .await
+    }
+
+    /// Adds the time played since this was last called and the score to the
+    /// save data in memory. It's written out by `save_progress`, or when the
+    /// window closes.
+    fn record_progress(&mut self) {
+        let now = Instant::now();
+        let played = now
+            .checked_duration_since(self.last_recorded)
+            .unwrap_or_default();
+        self.last_recorded = now;
+
+        let score = self.score;
+        save::update(|data| {
+            let profile = data.profile_mut();
+            profile.play_time += played;
+            if !launch::config().zen {
+                profile.record_score(
+                    &launch::config().theme,
+                    launch::config().difficulty.name(),
+                    score,
+                );
+            }
+        });
+    }
+
+    /// Records progress and writes the save data out in the background, at
+    /// most once every `SAVE_INTERVAL`. Anything newer is written by the next
+    /// save, or when the window closes.
+    fn save_progress(&mut self) {
+        self.record_progress();
+        let now = Instant::now();
+        let due = self.last_saved.map_or(true, |last_saved| {
+            now.checked_duration_since(last_saved).unwrap_or_default() >= SAVE_INTERVAL
+        });
+        if due {
+            self.last_saved = Some(now);
+            save::write_in_background();
+        }
+    }
+
+    /// Updates the measure and beat shown on the HUD when a new beat starts.
+    async fn report_position(&mut self) -> KludgineResult<()> {
+        let (measure, beat) = {
+            let scene_state = self.scene_state.read().await;
+            (scene_state.measure, scene_state.beat as usize)
+        };
+        if self.last_reported_beat != Some(beat) {
+            self.last_reported_beat = Some(beat);
+            self.send_to_hud(HudCommand::SetPosition { measure, beat })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the lead to a new output device, picking up where it left off.
+    async fn migrate_lead(&mut self) {
+        if let Some(lead_loop) = self.lead_loop {
+            if self.lead.as_ref().map_or(true, Voice::is_stale) {
+                let (_, beat) = lead_loop.position(self.scene_state.read().await.absolute_beat);
+                self.lead = Voice::play(lead_loop, beat, self.volume);
             }
         }
     }
@@ -194,14 +521,20 @@ impl Game {
 #[derive(Clone, Debug)]
 pub enum GameMessage {
     ElementEvent(ElementEvent),
+    MetronomeToggleClicked,
+}
+
+#[derive(Clone, Debug)]
+pub enum GameEvent {
+    ActiveKindsChanged(Vec<LoopKind>),
 }
 
 #[derive(Clone, Debug)]
 pub enum GameCommand {
     SetBeat {
         is_new_measure: bool,
-        beat: f32,
-        measure: usize,
+        absolute_beat: f32,
+        tempo: f32,
     },
 }
 
@@ -209,16 +542,48 @@ pub enum GameCommand {
 impl InteractiveComponent for Game {
     type Message = GameMessage;
     type Input = GameCommand;
-    type Output = ();
+    type Output = GameEvent;
 
     async fn receive_message(
         &mut self,
-        _context: &mut Context,
+        context: &mut Context,
         message: Self::Message,
     ) -> KludgineResult<()> {
         match message {
             GameMessage::ElementEvent(ElementEvent::LoopLockedIn) => {
-                self.pending_element = None;
+                self.add_score(LOCK_IN_SCORE).await?;
+                save::update(|data| data.profile_mut().lock_ins += 1);
+                self.save_progress();
+                if self.tutorial.record_lock_in() {
+                    self.hint_expires = None;
+                    self.set_help_text(self.tutorial.help_text().unwrap_or_default())
+                        .await?;
+                }
+                self.send_to_hud(HudCommand::SetProgress(None)).await?;
+                if let Some(pending_element) = self.pending_element.take() {
+                    if let Some(center) = self.center_of(pending_element.index()) {
+                        self.particles.send(ParticleCommand::Burst(center)).await?;
+                    }
+                    self.play_feedback(pending_element.index(), FeedbackAnimation::Success)
+                        .await?;
+                }
+            }
+            GameMessage::MetronomeToggleClicked => {
+                let settings =
+                    settings::update(|settings| settings.metronome = settings.metronome.next());
+                save::update(|data| data.metronome = settings.metronome);
+                save::write_in_background();
+                self.metronome_toggle
+                    .send(LabelCommand::SetValue(
+                        settings.metronome.label().to_string(),
+                    ))
+                    .await?;
+            }
+            GameMessage::ElementEvent(ElementEvent::ProgressLost) => {
+                if let Some(pending_element) = &self.pending_element {
+                    self.play_feedback(pending_element.index(), FeedbackAnimation::Fail)
+                        .await?;
+                }
             }
             GameMessage::ElementEvent(ElementEvent::Soloing(soloing_element)) => {
                 self.set_volume(QUIET_VOLUME);
@@ -240,11 +605,85 @@ impl InteractiveComponent for Game {
                         .await?;
                 }
             }
-            GameMessage::ElementEvent(ElementEvent::Success(window_position)) => {
-                self.clicks.send(ClickCommand::SetStatus { success: true, location: Some(window_position)}).await?;
+            GameMessage::ElementEvent(ElementEvent::Progress(progress)) => {
+                self.send_to_hud(HudCommand::SetProgress(Some(progress)))
+                    .await?;
+            }
+            GameMessage::ElementEvent(ElementEvent::Success {
+                location,
+                offset_millis,
+            }) => {
+                self.add_score(HIT_SCORE).await?;
+                self.record_offset(offset_millis).await?;
+                self.tutorial.record_success();
+                self.particles
+                    .send(ParticleCommand::Pulse(location))
+                    .await?;
+                self.clicks
+                    .send(ClickCommand::SetStatus {
+                        success: true,
+                        location: Some(location),
+                        offset_millis: Some(offset_millis),
+                    })
+                    .await?;
+            }
+            GameMessage::ElementEvent(ElementEvent::Failure {
+                location,
+                miss,
+                offset_millis,
+            }) => {
+                if let Some(offset_millis) = offset_millis {
+                    self.record_offset(offset_millis).await?;
+                }
+                self.clicks
+                    .send(ClickCommand::SetStatus {
+                        success: false,
+                        location,
+                        offset_millis,
+                    })
+                    .await?;
+                if let Some(hint) = self.tutorial.record_miss(miss) {
+                    self.show_hint(hint).await?;
+                }
+            }
+            GameMessage::ElementEvent(ElementEvent::Retired(retired)) => {
+                if let Some(spawned) = self
+                    .elements
+                    .iter()
+                    .find(|se| se.element.index() == retired)
+                {
+                    context.remove(&spawned.element).await;
+                }
+                self.depths.remove(retired);
+                self.elements.retain(|se| se.element.index() != retired);
+            }
+            GameMessage::ElementEvent(ElementEvent::DragReleased {
+                element,
+                window_position,
+                miss,
+                offset_millis,
+            }) => {
+                let miss = miss.or_else(|| {
+                    judge_drop(
+                        self.drop_target(window_position, element),
+                        self.has_other_elements(element),
+                    )
+                });
+                if let Some(dragged) = self
+                    .elements
+                    .iter()
+                    .find(|se| se.element.index() == element)
+                {
+                    dragged
+                        .element
+                        .send(ElementCommand::FinishDrag {
+                            miss,
+                            window_position,
+                            offset_millis,
+                        })
+                        .await?;
+                }
             }
-            GameMessage::ElementEvent(ElementEvent::Failure(window_position)) => {
-                self.clicks.send(ClickCommand::SetStatus { success: false, location: window_position}).await?;}
         }
 
         Ok(())
@@ -252,16 +691,41 @@ impl InteractiveComponent for Game {
 
     async fn receive_input(
         &mut self,
-        context: &mut Context,
+        _context: &mut Context,
         command: Self::Input,
     ) -> KludgineResult<()> {
         match command {
             GameCommand::SetBeat {
                 is_new_measure,
-                beat,
-                measure,
+                absolute_beat,
+                tempo,
             } => {
+                self.report_position().await?;
+                // The tutorial always clicks along while the player is learning
+                let mode = if self.tutorial.metronome_enabled() {
+                    MetronomeMode::EveryBeat
+                } else {
+                    settings::current().metronome
+                };
+                let pending = self.pending_element.as_ref().and_then(|pending| {
+                    self.elements
+                        .iter()
+                        .find(|se| se.element.index() == pending.index())
+                        .map(|se| se.audio_loop)
+                });
+                self.metronome.set_beat(absolute_beat, mode, pending);
+                self.migrate_lead().await;
+
                 if is_new_measure {
+                    self.start_section().await;
+                    let measure = self.scene_state.read().await.measure;
+                    self.expire_hint(measure).await?;
+                    if measure % MEASURES_PER_SECTION == 0 {
+                        self.save_progress();
+                    } else {
+                        self.record_progress();
+                    }
+
                     if self.pending_element.is_none() {
                         self.pick_next_spawn();
                     } else {
@@ -271,21 +735,24 @@ impl InteractiveComponent for Game {
 
                 for element in &self.elements {
                     if is_new_measure && element.being_destroyed {
-                        context.remove(&element.element).await;
-                    } else {
+                        // Retiring elements sink behind the rest as they fade out
+                        self.depths
+                            .set_depth(element.element.index(), Depth::new(Layer::Elements, -1));
                         element
                             .element
-                            .send(ElementCommand::SetBeat {
-                                beat,
-                                measure,
-                                is_new_measure,
+                            .send(ElementCommand::Retire {
+                                beats: RETIREMENT_BEATS,
                             })
                             .await?;
                     }
-                }
 
-                if is_new_measure {
-                    self.elements.retain(|e| !e.being_destroyed);
+                    element
+                        .element
+                        .send(ElementCommand::SetBeat {
+                            absolute_beat,
+                            tempo,
+                        })
+                        .await?;
                 }
             }
         }
@@ -297,21 +764,122 @@ impl InteractiveComponent for Game {
 #[async_trait]
 impl Component for Game {
     async fn initialize(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
-        self.help_text = self.new_entity(context, 
-            Label::new("Click on each new element to the rhythm you hear. \nRelax and enjoy the music.")
-        ).bounds(AbsoluteBounds {
+        self.help_text = self
+            .new_entity(
+                context,
+                Label::new(self.tutorial.help_text().unwrap_or_default()),
+            )
+            .insert()
+            .await?;
+        self.depths.insert(
+            self.help_text.index(),
+            Layer::Hud,
+            AbsoluteBounds {
                 left: Dimension::from_points(16.),
                 top: Dimension::from_points(16.),
                 right: Dimension::from_points(16.),
                 ..Default::default()
-            }).insert().await?;
-
-            self.clicks = self.new_entity(context, Clicks::default()).bounds(Surround::uniform(Dimension::from_points(0.)).into()).insert().await?;
+            },
+        );
+
+        self.metronome_toggle = self
+            .new_entity(context, Label::new(settings::current().metronome.label()))
+            .callback(|_| GameMessage::MetronomeToggleClicked)
+            .hover(Style {
+                color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+                ..Default::default()
+            })
+            .insert()
+            .await?;
+        self.depths.insert(
+            self.metronome_toggle.index(),
+            Layer::Hud,
+            AbsoluteBounds {
+                bottom: Dimension::from_points(16.),
+                ..Default::default()
+            },
+        );
+
+        let theme = Theme::current();
+        self.clicks = self
+            .new_entity(context, Clicks::new(theme.clicks.clone()))
+            .insert()
+            .await?;
+        self.depths.insert(
+            self.clicks.index(),
+            Layer::Feedback,
+            Surround::uniform(Dimension::from_points(0.)).into(),
+        );
+
+        self.particles = self
+            .new_entity(context, Particles::new(theme.particle.clone()))
+            .insert()
+            .await?;
+        self.depths.insert(
+            self.particles.index(),
+            Layer::Feedback,
+            Surround::uniform(Dimension::from_points(0.)).into(),
+        );
+
+        self.feedback = self
+            .new_entity(
+                context,
+                Feedback::new(theme.success.clone(), theme.fail.clone()),
+            )
+            .insert()
+            .await?;
+        self.depths.insert(
+            self.feedback.index(),
+            Layer::Feedback,
+            Surround::uniform(Dimension::from_points(0.)).into(),
+        );
+
+        if settings::current().show_hud && !launch::config().zen {
+            let hud = self.new_entity(context, Hud::default()).insert().await?;
+            self.depths.insert(
+                hud.index(),
+                Layer::Hud,
+                Surround::uniform(Dimension::from_points(0.)).into(),
+            );
+            self.hud = Some(hud);
+        }
         Ok(())
     }
 
+    async fn layout(
+        &mut self,
+        _context: &mut StyledContext,
+    ) -> KludgineResult<Box<dyn LayoutSolver>> {
+        self.depths.layout()
+    }
+
     async fn update(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
-            self.spawn_new_element(context).await?;
+        self.spawn_new_element(context).await?;
+        self.report_active_kinds(context).await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retiring_elements_fade_out_before_their_loop_restarts() {
+        // Retirement starts on a measure boundary, which is also where every
+        // loop starts a repetition
+        let measure_start = crate::assets::BEATS_PER_LOOP as f32 * 3.;
+        for audio_loop in Loop::all() {
+            let (iteration, beat) = audio_loop.position(measure_start);
+            assert_eq!(beat, 0., "{}", audio_loop.name);
+
+            let (faded_iteration, _) = audio_loop.position(measure_start + RETIREMENT_BEATS);
+            assert_eq!(iteration, faded_iteration, "{}", audio_loop.name);
+        }
+
+        // The hats restart twice a measure, but are gone before the second
+        let hats = Loop::named("Drums_hh");
+        assert_eq!(hats.position(measure_start + RETIREMENT_BEATS), (6, 8.));
+        assert_eq!(hats.position(measure_start + 16.), (7, 0.));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::{
+    assets::LoopKind,
+    palette,
+    timing::{self, TimingHistory},
+};
+use kludgine::prelude::*;
+
+/// Shows where the song is, how the pending element is coming along, the score,
+/// and how early or late recent clicks have been.
+#[derive(Default)]
+pub struct Hud {
+    position: Entity<Label>,
+    loops: Entity<Label>,
+    score: Entity<Label>,
+    progress: Entity<ProgressRing>,
+    timing: Entity<TimingGraph>,
+    timing_summary: Entity<Label>,
+}
+
+#[derive(Clone, Debug)]
+pub enum HudCommand {
+    SetPosition {
+        measure: usize,
+        beat: usize,
+    },
+    SetActiveKinds(Vec<LoopKind>),
+    SetScore {
+        score: u64,
+        /// The best score from previous games, if there is one
+        best: Option<u64>,
+    },
+    /// The pending element's progress towards locking in, if there is one
+    SetProgress(Option<f32>),
+    SetTiming(TimingHistory),
+}
+
+#[async_trait]
+impl Component for Hud {
+    async fn initialize(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
+        self.position = self
+            .new_entity(context, Label::new("Measure 1 - Beat 1"))
+            .insert()
+            .await?;
+        self.loops = self.new_entity(context, Label::new("")).insert().await?;
+        self.score = self
+            .new_entity(context, Label::new("Score 0"))
+            .insert()
+            .await?;
+        self.progress = self
+            .new_entity(context, ProgressRing::default())
+            .insert()
+            .await?;
+        self.timing = self
+            .new_entity(context, TimingGraph::default())
+            .insert()
+            .await?;
+        self.timing_summary = self.new_entity(context, Label::new("")).insert().await?;
+        Ok(())
+    }
+
+    async fn layout(
+        &mut self,
+        _context: &mut StyledContext,
+    ) -> KludgineResult<Box<dyn LayoutSolver>> {
+        Layout::absolute()
+            .child(
+                &self.position,
+                AbsoluteBounds {
+                    left: Dimension::from_points(16.),
+                    bottom: Dimension::from_points(16.),
+                    ..Default::default()
+                },
+            )?
+            .child(
+                &self.loops,
+                AbsoluteBounds {
+                    right: Dimension::from_points(16.),
+                    bottom: Dimension::from_points(16.),
+                    ..Default::default()
+                },
+            )?
+            .child(
+                &self.score,
+                AbsoluteBounds {
+                    right: Dimension::from_points(16.),
+                    top: Dimension::from_points(64.),
+                    ..Default::default()
+                },
+            )?
+            .child(
+                &self.progress,
+                AbsoluteBounds {
+                    right: Dimension::from_points(16.),
+                    top: Dimension::from_points(96.),
+                    width: Dimension::from_points(48.),
+                    height: Dimension::from_points(48.),
+                    ..Default::default()
+                },
+            )?
+            .child(
+                &self.timing,
+                AbsoluteBounds {
+                    right: Dimension::from_points(16.),
+                    top: Dimension::from_points(160.),
+                    width: Dimension::from_points(TIMING_BAR_WIDTH * timing::BUCKETS as f32),
+                    height: Dimension::from_points(32.),
+                    ..Default::default()
+                },
+            )?
+            .child(
+                &self.timing_summary,
+                AbsoluteBounds {
+                    right: Dimension::from_points(16.),
+                    top: Dimension::from_points(196.),
+                    ..Default::default()
+                },
+            )?
+            .layout()
+    }
+}
+
+#[async_trait]
+impl InteractiveComponent for Hud {
+    type Message = ();
+    type Input = HudCommand;
+    type Output = ();
+
+    async fn receive_input(
+        &mut self,
+        _context: &mut Context,
+        command: Self::Input,
+    ) -> KludgineResult<()> {
+        match command {
+            HudCommand::SetPosition { measure, beat } => {
+                self.position
+                    .send(LabelCommand::SetValue(format!(
+                        "Measure {} - Beat {}",
+                        measure + 1,
+                        beat + 1
+                    )))
+                    .await?;
+            }
+            HudCommand::SetActiveKinds(kinds) => {
+                let names = kinds
+                    .iter()
+                    .map(|kind| format!("{:?}", kind))
+                    .collect::<Vec<_>>();
+                self.loops
+                    .send(LabelCommand::SetValue(names.join("\n")))
+                    .await?;
+            }
+            HudCommand::SetScore { score, best } => {
+                let text = match best {
+                    Some(best) => format!("Score {}\nBest {}", score, best.max(score)),
+                    None => format!("Score {}", score),
+                };
+                self.score.send(LabelCommand::SetValue(text)).await?;
+            }
+            HudCommand::SetProgress(progress) => {
+                self.progress
+                    .send(ProgressRingCommand::SetProgress(progress))
+                    .await?;
+            }
+            HudCommand::SetTiming(history) => {
+                let summary = history
+                    .mean()
+                    .map(|mean| format!("Average {}", timing::describe_offset(mean)))
+                    .unwrap_or_default();
+                self.timing_summary
+                    .send(LabelCommand::SetValue(summary))
+                    .await?;
+                self.timing
+                    .send(TimingGraphCommand::SetHistogram(history.histogram()))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A ring of dots that light up as progress is made.
+#[derive(Default)]
+pub struct ProgressRing {
+    progress: Option<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ProgressRingCommand {
+    SetProgress(Option<f32>),
+}
+
+const RING_DOTS: usize = 16;
+
+#[async_trait]
+impl Component for ProgressRing {
+    async fn render(&self, context: &mut StyledContext, layout: &Layout) -> KludgineResult<()> {
+        if let Some(progress) = self.progress {
+            let bounds = layout.inner_bounds();
+            let radius = bounds.size.width.to_f32().min(bounds.size.height.to_f32()) / 2.;
+            let center_x = bounds.origin.x.to_f32() + bounds.size.width.to_f32() / 2.;
+            let center_y = bounds.origin.y.to_f32() + bounds.size.height.to_f32() / 2.;
+            let lit_dots = (progress * RING_DOTS as f32).round() as usize;
+            let palette = palette::current();
+
+            for dot in 0..RING_DOTS {
+                // Start at the top and fill in clockwise
+                let angle = -std::f32::consts::FRAC_PI_2
+                    + std::f32::consts::PI * 2. * dot as f32 / RING_DOTS as f32;
+                let color = if dot < lit_dots {
+                    palette.accent
+                } else {
+                    palette.inactive
+                };
+                Shape::circle(
+                    Point::new(
+                        Points::from_f32(center_x + angle.cos() * (radius - 3.)),
+                        Points::from_f32(center_y + angle.sin() * (radius - 3.)),
+                    ),
+                    Points::from_f32(3.),
+                )
+                .fill(Fill::new(color))
+                .render_at(Point::default(), context.scene())
+                .await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InteractiveComponent for ProgressRing {
+    type Message = ();
+    type Input = ProgressRingCommand;
+    type Output = ();
+
+    async fn receive_input(
+        &mut self,
+        _context: &mut Context,
+        command: Self::Input,
+    ) -> KludgineResult<()> {
+        match command {
+            ProgressRingCommand::SetProgress(progress) => {
+                self.progress = progress;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A histogram of recent click offsets, early on the left and late on the right.
+#[derive(Default)]
+pub struct TimingGraph {
+    histogram: [usize; timing::BUCKETS],
+}
+
+#[derive(Clone, Debug)]
+pub enum TimingGraphCommand {
+    SetHistogram([usize; timing::BUCKETS]),
+}
+
+const TIMING_BAR_WIDTH: f32 = 6.;
+
+#[async_trait]
+impl Component for TimingGraph {
+    async fn render(&self, context: &mut StyledContext, layout: &Layout) -> KludgineResult<()> {
+        let tallest = self.histogram.iter().copied().max().unwrap_or_default();
+        if tallest == 0 {
+            return Ok(());
+        }
+
+        let bounds = layout.inner_bounds();
+        let height = bounds.size.height.to_f32();
+        let bottom = bounds.origin.y.to_f32() + height;
+        let palette = palette::current();
+        for (bucket, &count) in self.histogram.iter().enumerate() {
+            let bar_height = height * count as f32 / tallest as f32;
+            // The middle bar is on the beat
+            let color = if bucket == timing::BUCKETS / 2 {
+                palette.accent
+            } else {
+                Color::new(1.0, 1.0, 1.0, 0.6)
+            };
+            Shape::rect(Rect::new(
+                Point::new(
+                    Points::from_f32(bounds.origin.x.to_f32() + TIMING_BAR_WIDTH * bucket as f32),
+                    Points::from_f32(bottom - bar_height),
+                ),
+                Size::new(
+                    Points::from_f32(TIMING_BAR_WIDTH - 1.),
+                    Points::from_f32(bar_height),
+                ),
+            ))
+            .fill(Fill::new(color))
+            .render_at(Point::default(), context.scene())
+            .await;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InteractiveComponent for TimingGraph {
+    type Message = ();
+    type Input = TimingGraphCommand;
+    type Output = ();
+
+    async fn receive_input(
+        &mut self,
+        _context: &mut Context,
+        command: Self::Input,
+    ) -> KludgineResult<()> {
+        match command {
+            TimingGraphCommand::SetHistogram(histogram) => {
+                self.histogram = histogram;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,615 @@
+use crate::assets::NoteKind;
+use std::{collections::VecDeque, time::Instant};
+
+/// How far from the beat a press counts as a hit by default, in milliseconds.
+const DEFAULT_HIT_WINDOW_MILLIS: i128 = 150;
+/// How far ahead of the hit window an early press still uses up the beat, in
+/// milliseconds.
+const EARLY_GRACE_MILLIS: i128 = 50;
+/// How long after a beat it's given up on, in milliseconds.
+const SKIP_AFTER_MILLIS: i128 = 100;
+/// How far from the release beat a sustained note can be let go, in milliseconds.
+const RELEASE_WINDOW_MILLIS: i128 = 200;
+
+/// How much progress a hit tap note is worth.
+const HIT_FACTOR: f32 = 1.;
+/// How much progress each half of a sustained note is worth.
+const SUSTAIN_FACTOR: f32 = 0.5;
+/// How much progress a miss costs.
+const MISS_FACTOR: f32 = -0.5;
+
+/// Why a beat wasn't hit successfully.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Miss {
+    Early,
+    Late,
+    /// The beat passed without being clicked
+    Skipped,
+    /// A drag note was released somewhere other than a drop target
+    WrongTarget,
+}
+
+/// How a press, release or missed beat was judged. Offsets are how far from
+/// the beat it happened, in milliseconds: negative is early, positive is late.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Judgement {
+    Hit {
+        offset_millis: i64,
+    },
+    Missed {
+        miss: Miss,
+        /// Missing for beats that were never clicked
+        offset_millis: Option<i64>,
+    },
+}
+
+/// A judgement and how much it moves an element's progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outcome {
+    pub judgement: Judgement,
+    pub factor: f32,
+}
+
+impl Outcome {
+    fn hit(offset_millis: i64, factor: f32) -> Self {
+        Self {
+            judgement: Judgement::Hit { offset_millis },
+            factor,
+        }
+    }
+
+    fn missed(miss: Miss, offset_millis: Option<i64>) -> Self {
+        Self {
+            judgement: Judgement::Missed {
+                miss,
+                offset_millis,
+            },
+            factor: MISS_FACTOR,
+        }
+    }
+
+    /// Judges the end of a sustained note, given why it was missed, if it was.
+    pub fn sustain_finished(miss: Option<Miss>, offset_millis: Option<i64>) -> Self {
+        match miss {
+            Some(miss) => Self::missed(miss, offset_millis),
+            None => Self::hit(offset_millis.unwrap_or_default(), SUSTAIN_FACTOR),
+        }
+    }
+}
+
+/// The result of letting go of a sustained note.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Release {
+    pub kind: NoteKind,
+    pub miss: Option<Miss>,
+    pub offset_millis: Option<i64>,
+}
+
+/// A note from the beat map, scheduled against the clock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduledNote {
+    pub hit: Instant,
+    pub release: Option<Instant>,
+    pub kind: NoteKind,
+}
+
+/// The notes waiting to be hit, and the sustained note being held, if any.
+#[derive(Debug)]
+pub struct BeatQueue {
+    notes: VecDeque<ScheduledNote>,
+    holding: Option<ScheduledNote>,
+    /// How far from the beat a press counts as a hit, in milliseconds
+    hit_window_millis: i128,
+}
+
+impl Default for BeatQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_HIT_WINDOW_MILLIS)
+    }
+}
+
+impl BeatQueue {
+    pub fn new(hit_window_millis: i128) -> Self {
+        Self {
+            notes: VecDeque::default(),
+            holding: None,
+            hit_window_millis,
+        }
+    }
+
+    pub fn push(&mut self, note: ScheduledNote) {
+        self.notes.push_back(note);
+    }
+
+    /// The next note to hit.
+    pub fn front(&self) -> Option<&ScheduledNote> {
+        self.notes.front()
+    }
+
+    pub fn is_holding(&self) -> bool {
+        self.holding.is_some()
+    }
+
+    /// When each note is due, for the debug overlay.
+    pub fn snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot {
+            held: self.holding.map(|note| note.release.unwrap_or(note.hit)),
+            queued: self.notes.iter().map(|note| note.hit).collect(),
+        }
+    }
+
+    /// Judges a press against the next note.
+    pub fn press(&mut self, now: Instant) -> Option<Outcome> {
+        let note = self.notes.pop_front()?;
+        let delta = millis_until(note.hit, now);
+        let offset_millis = -delta as i64;
+        let outcome = if delta.abs() <= self.hit_window_millis {
+            if note.release.is_some() {
+                // Sustained notes earn the rest of their progress when released
+                self.holding = Some(note);
+                Outcome::hit(offset_millis, SUSTAIN_FACTOR)
+            } else {
+                Outcome::hit(offset_millis, HIT_FACTOR)
+            }
+        } else if delta < 0 {
+            // Missed the beat entirely
+            Outcome::missed(Miss::Late, Some(offset_millis))
+        } else {
+            if delta > self.hit_window_millis + EARLY_GRACE_MILLIS {
+                // Far in the future, the click should count against the player
+                // but the beat should still be clickable.
+                self.notes.push_front(note);
+            }
+            Outcome::missed(Miss::Early, Some(offset_millis))
+        };
+        Some(outcome)
+    }
+
+    /// Lets go of the note being held, if there is one.
+    pub fn release(&mut self, now: Instant) -> Option<Release> {
+        let note = self.holding.take()?;
+        let delta = note.release.map(|release| millis_until(release, now));
+        let miss = delta.and_then(|delta| {
+            if delta.abs() <= RELEASE_WINDOW_MILLIS {
+                None
+            } else if delta > 0 {
+                Some(Miss::Early)
+            } else {
+                Some(Miss::Late)
+            }
+        });
+        Some(Release {
+            kind: note.kind,
+            miss,
+            offset_millis: delta.map(|delta| -delta as i64),
+        })
+    }
+
+    /// Gives up on the next note if its beat has passed, and on the note being
+    /// held if it has been held for too long.
+    pub fn deduct_missed(&mut self, now: Instant) -> Vec<Outcome> {
+        let mut outcomes = Vec::new();
+
+        if let Some(note) = self.notes.front() {
+            if millis_until(note.hit, now) < -SKIP_AFTER_MILLIS {
+                self.notes.pop_front();
+                outcomes.push(Outcome::missed(Miss::Skipped, None));
+            }
+        }
+
+        if let Some(release) = self.holding.and_then(|note| note.release) {
+            if millis_until(release, now) < -RELEASE_WINDOW_MILLIS {
+                // Held on for too long
+                self.holding = None;
+                outcomes.push(Outcome::missed(Miss::Late, None));
+            }
+        }
+
+        outcomes
+    }
+}
+
+/// How an element's progress changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressChange {
+    Progress(f32),
+    LockedIn,
+    /// The element was already locked in
+    Unchanged,
+}
+
+/// How close an element is to locking in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Progress {
+    Pending(f32),
+    LockedIn,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress::Pending(0.)
+    }
+}
+
+impl Progress {
+    pub fn percent(&self) -> f32 {
+        match self {
+            Progress::Pending(value) => *value,
+            Progress::LockedIn => 1.,
+        }
+    }
+
+    pub fn min_percent(&self) -> f32 {
+        match self {
+            Progress::Pending(value) => value / 3. * 0.9 + 0.1,
+            Progress::LockedIn => 1.,
+        }
+    }
+
+    /// Adds `factor` hits' worth of progress, for a loop with `beats_per_loop`
+    /// beats. Hitting half of a loop's beats locks an element in.
+    pub fn add(&mut self, factor: f32, beats_per_loop: usize) -> ProgressChange {
+        if let Progress::Pending(current_progress) = *self {
+            let progress = current_progress + 1. / (beats_per_loop as f32 / 2.) * factor;
+
+            if progress >= 1. {
+                *self = Progress::LockedIn;
+                ProgressChange::LockedIn
+            } else {
+                let progress = progress.max(0.);
+                *self = Progress::Pending(progress);
+                ProgressChange::Progress(progress)
+            }
+        } else {
+            ProgressChange::Unchanged
+        }
+    }
+}
+
+/// When the notes in a `BeatQueue` are due: the release of the note being
+/// held, if any, and the hit of each note waiting.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueSnapshot {
+    pub held: Option<Instant>,
+    pub queued: Vec<Instant>,
+}
+
+impl QueueSnapshot {
+    /// Describes the queue as the milliseconds from `now` until each note. The
+    /// note being held, if any, comes first in brackets.
+    pub fn describe(&self, now: Instant) -> String {
+        let held = self
+            .held
+            .iter()
+            .map(|release| format!("[{}]", millis_until(*release, now)));
+        let queued = self
+            .queued
+            .iter()
+            .map(|hit| millis_until(*hit, now).to_string());
+        held.chain(queued).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// What a drag note was released over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DropTarget {
+    OtherElement,
+    /// The element the drag started on
+    Itself,
+    Nowhere,
+}
+
+/// Judges where a drag note was released. Drags belong on another element,
+/// but when there isn't one on screen, dropping back on the element itself
+/// counts too, so that an element on its own can still be locked in.
+pub fn judge_drop(target: DropTarget, other_elements: bool) -> Option<Miss> {
+    match target {
+        DropTarget::OtherElement => None,
+        DropTarget::Itself if !other_elements => None,
+        _ => Some(Miss::WrongTarget),
+    }
+}
+
+/// How many milliseconds from `now` until `instant`, negative if it has passed.
+pub fn millis_until(instant: Instant, now: Instant) -> i128 {
+    if let Some(delta) = instant.checked_duration_since(now) {
+        delta.as_millis() as i128
+    } else {
+        -(now.saturating_duration_since(instant).as_millis() as i128)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn start() -> Instant {
+        // Far enough in the future that subtracting from it never underflows
+        Instant::now() + Duration::from_secs(60)
+    }
+
+    fn at(base: Instant, offset_millis: i64) -> Instant {
+        if offset_millis >= 0 {
+            base + Duration::from_millis(offset_millis as u64)
+        } else {
+            base - Duration::from_millis(-offset_millis as u64)
+        }
+    }
+
+    fn tap(hit: Instant) -> ScheduledNote {
+        ScheduledNote {
+            hit,
+            release: None,
+            kind: NoteKind::Tap,
+        }
+    }
+
+    fn hold(hit: Instant, release: Instant) -> ScheduledNote {
+        ScheduledNote {
+            hit,
+            release: Some(release),
+            kind: NoteKind::Hold { length: 1. },
+        }
+    }
+
+    fn press_at(offset_millis: i64) -> (BeatQueue, Outcome) {
+        let beat = start();
+        let mut queue = BeatQueue::default();
+        queue.push(tap(beat));
+        let outcome = queue.press(at(beat, offset_millis)).unwrap();
+        (queue, outcome)
+    }
+
+    #[test]
+    fn on_the_beat_is_a_hit() {
+        let (queue, outcome) = press_at(0);
+        assert_eq!(outcome, Outcome::hit(0, HIT_FACTOR));
+        assert!(queue.front().is_none());
+    }
+
+    #[test]
+    fn early_clicks() {
+        let (_, outcome) = press_at(-150);
+        assert_eq!(outcome, Outcome::hit(-150, HIT_FACTOR));
+
+        let (queue, outcome) = press_at(-151);
+        assert_eq!(outcome, Outcome::missed(Miss::Early, Some(-151)));
+        assert!(queue.front().is_none());
+    }
+
+    #[test]
+    fn late_clicks() {
+        let (_, outcome) = press_at(150);
+        assert_eq!(outcome, Outcome::hit(150, HIT_FACTOR));
+
+        let (queue, outcome) = press_at(151);
+        assert_eq!(outcome, Outcome::missed(Miss::Late, Some(151)));
+        assert!(queue.front().is_none());
+    }
+
+    #[test]
+    fn slightly_early_clicks_use_up_the_beat() {
+        for offset in -200..=-151 {
+            let (queue, outcome) = press_at(offset);
+            assert_eq!(outcome, Outcome::missed(Miss::Early, Some(offset)));
+            assert!(queue.front().is_none(), "{} kept the beat", offset);
+        }
+    }
+
+    #[test]
+    fn very_early_clicks_keep_the_beat() {
+        let beat = start();
+        let mut queue = BeatQueue::default();
+        queue.push(tap(beat));
+
+        let outcome = queue.press(at(beat, -201)).unwrap();
+        assert_eq!(outcome, Outcome::missed(Miss::Early, Some(-201)));
+        assert_eq!(queue.front(), Some(&tap(beat)));
+
+        // The beat can still be hit afterwards
+        let outcome = queue.press(beat).unwrap();
+        assert_eq!(outcome, Outcome::hit(0, HIT_FACTOR));
+    }
+
+    #[test]
+    fn hit_windows_can_be_narrowed() {
+        let beat = start();
+        let mut queue = BeatQueue::new(100);
+        queue.push(tap(beat));
+        queue.push(tap(at(beat, 1000)));
+
+        let outcome = queue.press(at(beat, 101)).unwrap();
+        assert_eq!(outcome, Outcome::missed(Miss::Late, Some(101)));
+
+        // The early grace period shrinks along with the window
+        let outcome = queue.press(at(beat, 1000 - 151)).unwrap();
+        assert_eq!(outcome, Outcome::missed(Miss::Early, Some(-151)));
+        assert!(queue.front().is_some());
+    }
+
+    #[test]
+    fn pressing_with_nothing_queued() {
+        let mut queue = BeatQueue::default();
+        assert_eq!(queue.press(start()), None);
+    }
+
+    #[test]
+    fn describing_the_queue() {
+        let beat = start();
+        let mut queue = BeatQueue::default();
+        queue.push(hold(beat, at(beat, 500)));
+        queue.push(tap(at(beat, 1000)));
+        let snapshot = queue.snapshot();
+        assert_eq!(snapshot.describe(beat), "0 1000");
+        // The times count down as the snapshot ages
+        assert_eq!(snapshot.describe(at(beat, 250)), "-250 750");
+
+        queue.press(beat).unwrap();
+        assert_eq!(queue.snapshot().describe(at(beat, 100)), "[400] 900");
+    }
+
+    #[test]
+    fn sustained_notes() {
+        let beat = start();
+        let release = at(beat, 500);
+        let mut queue = BeatQueue::default();
+        queue.push(hold(beat, release));
+
+        let outcome = queue.press(at(beat, 20)).unwrap();
+        assert_eq!(outcome, Outcome::hit(20, SUSTAIN_FACTOR));
+        assert!(queue.is_holding());
+
+        let released = queue.release(at(release, -200)).unwrap();
+        assert_eq!(released.miss, None);
+        assert_eq!(released.offset_millis, Some(-200));
+        assert!(!queue.is_holding());
+        assert_eq!(queue.release(release), None);
+    }
+
+    #[test]
+    fn sustained_notes_released_outside_the_window() {
+        let beat = start();
+        let release = at(beat, 500);
+        let mut queue = BeatQueue::default();
+
+        queue.push(hold(beat, release));
+        queue.press(beat);
+        let released = queue.release(at(release, -201)).unwrap();
+        assert_eq!(released.miss, Some(Miss::Early));
+
+        queue.push(hold(beat, release));
+        queue.press(beat);
+        let released = queue.release(at(release, 201)).unwrap();
+        assert_eq!(released.miss, Some(Miss::Late));
+    }
+
+    #[test]
+    fn finishing_sustained_notes() {
+        assert_eq!(
+            Outcome::sustain_finished(None, Some(30)),
+            Outcome::hit(30, SUSTAIN_FACTOR)
+        );
+        assert_eq!(
+            Outcome::sustain_finished(Some(Miss::WrongTarget), Some(30)),
+            Outcome::missed(Miss::WrongTarget, Some(30))
+        );
+    }
+
+    #[test]
+    fn drags_belong_on_other_elements() {
+        assert_eq!(judge_drop(DropTarget::OtherElement, true), None);
+        assert_eq!(
+            judge_drop(DropTarget::Itself, true),
+            Some(Miss::WrongTarget)
+        );
+        assert_eq!(
+            judge_drop(DropTarget::Nowhere, true),
+            Some(Miss::WrongTarget)
+        );
+    }
+
+    #[test]
+    fn drags_on_the_only_element_can_land_on_itself() {
+        assert_eq!(judge_drop(DropTarget::Itself, false), None);
+        assert_eq!(
+            judge_drop(DropTarget::Nowhere, false),
+            Some(Miss::WrongTarget)
+        );
+    }
+
+    #[test]
+    fn missed_beats_are_deducted() {
+        let beat = start();
+        let mut queue = BeatQueue::default();
+        queue.push(tap(beat));
+
+        assert!(queue.deduct_missed(at(beat, 100)).is_empty());
+        assert_eq!(queue.front(), Some(&tap(beat)));
+
+        assert_eq!(
+            queue.deduct_missed(at(beat, 101)),
+            vec![Outcome::missed(Miss::Skipped, None)]
+        );
+        assert!(queue.front().is_none());
+        assert!(queue.deduct_missed(at(beat, 1000)).is_empty());
+    }
+
+    #[test]
+    fn only_one_missed_beat_is_deducted_at_a_time() {
+        let beat = start();
+        let mut queue = BeatQueue::default();
+        queue.push(tap(beat));
+        queue.push(tap(at(beat, 10)));
+
+        let now = at(beat, 500);
+        assert_eq!(queue.deduct_missed(now).len(), 1);
+        assert_eq!(queue.deduct_missed(now).len(), 1);
+        assert!(queue.deduct_missed(now).is_empty());
+    }
+
+    #[test]
+    fn holding_too_long_is_deducted() {
+        let beat = start();
+        let release = at(beat, 500);
+        let mut queue = BeatQueue::default();
+        queue.push(hold(beat, release));
+        queue.press(beat);
+
+        assert!(queue.deduct_missed(at(release, 200)).is_empty());
+        assert!(queue.is_holding());
+        assert_eq!(
+            queue.deduct_missed(at(release, 201)),
+            vec![Outcome::missed(Miss::Late, None)]
+        );
+        assert!(!queue.is_holding());
+    }
+
+    #[test]
+    fn progress_accumulates_and_locks_in() {
+        let mut progress = Progress::default();
+        for hit in 1..8 {
+            assert_eq!(
+                progress.add(HIT_FACTOR, 16),
+                ProgressChange::Progress(hit as f32 / 8.)
+            );
+        }
+        assert_eq!(progress.add(HIT_FACTOR, 16), ProgressChange::LockedIn);
+        assert_eq!(progress, Progress::LockedIn);
+        assert_eq!(progress.percent(), 1.);
+
+        // Nothing changes once locked in
+        assert_eq!(progress.add(MISS_FACTOR, 16), ProgressChange::Unchanged);
+        assert_eq!(progress, Progress::LockedIn);
+    }
+
+    #[test]
+    fn progress_never_goes_negative() {
+        let mut progress = Progress::default();
+        assert_eq!(progress.add(MISS_FACTOR, 16), ProgressChange::Progress(0.));
+        progress.add(HIT_FACTOR, 16);
+        assert_eq!(
+            progress.add(MISS_FACTOR, 16),
+            ProgressChange::Progress(0.0625)
+        );
+    }
+
+    #[test]
+    fn sustained_notes_earn_a_full_hit() {
+        let mut sustained = Progress::default();
+        sustained.add(SUSTAIN_FACTOR, 16);
+        sustained.add(SUSTAIN_FACTOR, 16);
+
+        let mut tapped = Progress::default();
+        tapped.add(HIT_FACTOR, 16);
+        assert_eq!(sustained, tapped);
+    }
+
+    #[test]
+    fn millis_until_is_signed() {
+        let now = start();
+        assert_eq!(millis_until(at(now, 42), now), 42);
+        assert_eq!(millis_until(at(now, -42), now), -42);
+        assert_eq!(millis_until(now, now), 0);
+    }
+}
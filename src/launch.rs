@@ -0,0 +1,273 @@
+use crate::{assets, save, theme::Theme};
+use anyhow::Context as _;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, str::FromStr};
+use structopt::StructOpt;
+
+/// The themes that can be chosen with `--theme`.
+const THEMES: &[&str] = &[Theme::SPACE_NAME];
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "chillscapes", about = "A relaxing rhythm game")]
+pub struct Args {
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+    /// The theme to play
+    #[structopt(long, possible_values = THEMES)]
+    theme: Option<String>,
+    /// Seeds the random choices made while playing, such as which loops spawn
+    #[structopt(long)]
+    seed: Option<u64>,
+    /// The starting tempo, in beats per minute. Loops are resampled to match,
+    /// so tempos away from the pack's also shift the pitch
+    #[structopt(long)]
+    tempo: Option<f32>,
+    /// How far the tempo can wander from the starting tempo each section, in
+    /// beats per minute. Loops are resampled to match, so this also shifts
+    /// their pitch
+    #[structopt(long)]
+    tempo_drift: Option<f32>,
+    /// How forgiving the timing is: relaxed, normal or challenging
+    #[structopt(long)]
+    difficulty: Option<Difficulty>,
+    /// Starts playing right away instead of showing the title screen
+    #[structopt(long)]
+    skip_title: bool,
+    /// Just the music: no HUD, tutorial or scores
+    #[structopt(long)]
+    zen: bool,
+    #[structopt(long, conflicts_with = "fullscreen")]
+    windowed: bool,
+    #[structopt(long)]
+    fullscreen: bool,
+    /// Plays the same song as a previous game, from a replay file
+    #[structopt(long, parse(from_os_str))]
+    replay: Option<PathBuf>,
+    /// Reads defaults for these options from a TOML file
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+}
+
+/// Tools that run instead of the game.
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Checks that every asset in a pack loads and matches its declared
+    /// timing, exiting with an error if any don't
+    Validate {
+        #[structopt(possible_values = THEMES)]
+        pack: String,
+    },
+}
+
+/// How forgiving the timing is.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Relaxed,
+    Normal,
+    Challenging,
+}
+
+impl Difficulty {
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Relaxed => "relaxed",
+            Difficulty::Normal => "normal",
+            Difficulty::Challenging => "challenging",
+        }
+    }
+
+    /// How far from the beat a press counts as a hit, in milliseconds.
+    pub fn hit_window_millis(self) -> i128 {
+        match self {
+            Difficulty::Relaxed => 200,
+            Difficulty::Normal => 150,
+            Difficulty::Challenging => 100,
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relaxed" => Ok(Difficulty::Relaxed),
+            "normal" => Ok(Difficulty::Normal),
+            "challenging" => Ok(Difficulty::Challenging),
+            _ => Err(format!("unknown difficulty {:?}", s)),
+        }
+    }
+}
+
+/// How the game was launched, from the command line and configuration file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchConfig {
+    pub theme: String,
+    pub seed: u64,
+    pub tempo: f32,
+    /// How far the tempo can wander each section, in beats per minute
+    pub tempo_drift: f32,
+    pub difficulty: Difficulty,
+    pub skip_title: bool,
+    pub zen: bool,
+    pub fullscreen: bool,
+}
+
+impl Default for LaunchConfig {
+    fn default() -> Self {
+        Self {
+            theme: THEMES[0].to_string(),
+            seed: rand::random(),
+            tempo: assets::TEMPO,
+            tempo_drift: 0.,
+            difficulty: Difficulty::Normal,
+            skip_title: false,
+            zen: false,
+            fullscreen: false,
+        }
+    }
+}
+
+/// Everything needed to play the same song again.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub theme: String,
+    pub seed: u64,
+    pub tempo: f32,
+    pub difficulty: Difficulty,
+}
+
+impl LaunchConfig {
+    /// Builds the launch configuration from the command line. Options given
+    /// on the command line win over a replay, which wins over the
+    /// configuration file.
+    pub fn new(args: Args) -> anyhow::Result<Self> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .with_context(|| format!("reading {}", path.display()))?;
+                toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))?
+            }
+            None => Self::default(),
+        };
+
+        if let Some(path) = &args.replay {
+            let contents =
+                fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+            let replay: Replay = serde_json::from_str(&contents)
+                .with_context(|| format!("parsing {}", path.display()))?;
+            config.theme = replay.theme;
+            config.seed = replay.seed;
+            config.tempo = replay.tempo;
+            config.difficulty = replay.difficulty;
+        }
+
+        if let Some(theme) = args.theme {
+            config.theme = theme;
+        }
+        if let Some(seed) = args.seed {
+            config.seed = seed;
+        }
+        if let Some(tempo) = args.tempo {
+            config.tempo = tempo;
+        }
+        if let Some(tempo_drift) = args.tempo_drift {
+            config.tempo_drift = tempo_drift;
+        }
+        if let Some(difficulty) = args.difficulty {
+            config.difficulty = difficulty;
+        }
+        config.skip_title |= args.skip_title;
+        config.zen |= args.zen;
+        if args.fullscreen {
+            config.fullscreen = true;
+        } else if args.windowed {
+            config.fullscreen = false;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the options that came from files as well as the command line,
+    /// which structopt can't.
+    fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            THEMES.contains(&self.theme.as_str()),
+            "unknown theme {:?}",
+            self.theme
+        );
+        anyhow::ensure!(
+            self.tempo.is_finite() && self.tempo > 0.,
+            "the tempo must be above 0, not {}",
+            self.tempo
+        );
+        anyhow::ensure!(
+            self.tempo_drift.is_finite() && self.tempo_drift >= 0. && self.tempo_drift < self.tempo,
+            "the tempo drift must be at least 0 and below the tempo, not {}",
+            self.tempo_drift
+        );
+        Ok(())
+    }
+
+    pub fn replay(&self) -> Replay {
+        Replay {
+            theme: self.theme.clone(),
+            seed: self.seed,
+            tempo: self.tempo,
+            difficulty: self.difficulty,
+        }
+    }
+
+    /// Writes a replay of this game to the data directory, so the last song
+    /// can always be played again with `--replay`.
+    pub fn write_last_replay(&self) -> anyhow::Result<()> {
+        let dir = save::data_dir().context("no user data directory")?;
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join("last-replay.json"),
+            serde_json::to_string_pretty(&self.replay())?,
+        )?;
+        Ok(())
+    }
+}
+
+static CONFIG: OnceCell<LaunchConfig> = OnceCell::new();
+
+/// Sets the launch configuration. Only the first call has any effect.
+pub fn initialize(config: LaunchConfig) {
+    let _ = CONFIG.set(config);
+}
+
+/// The launch configuration, or the defaults if none was set.
+pub fn config() -> &'static LaunchConfig {
+    CONFIG.get_or_init(LaunchConfig::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tempos_from_files_are_checked() {
+        let config: LaunchConfig = toml::from_str("tempo = 0.0").unwrap();
+        assert!(config.validate().is_err());
+
+        let replay = LaunchConfig {
+            tempo: f32::INFINITY,
+            ..LaunchConfig::default()
+        };
+        assert!(replay.validate().is_err());
+
+        assert!(LaunchConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn themes_from_files_are_checked() {
+        let config: LaunchConfig = toml::from_str("theme = \"ocean\"").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
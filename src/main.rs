@@ -1,20 +1,92 @@
 #![windows_subsystem = "windows"]
-use kludgine::prelude::*;
+use kludgine::{prelude::*, winit::window::Fullscreen};
 mod assets;
+mod audio;
+mod backdrop;
 mod clicks;
+mod clock;
+mod cursor;
+mod debug;
+mod depth;
 mod element;
+mod error;
+mod feedback;
 mod game;
+mod hud;
+mod judge;
+mod launch;
+mod metronome;
+mod palette;
+mod particles;
+mod random;
+mod save;
+mod settings;
+mod tempo;
+mod theme;
+mod timing;
 mod title;
+mod tutorial;
+mod validate;
 use assets::{Loop, LoopKind};
-use game::{Game, GameCommand};
+use audio::Voice;
+use backdrop::{Backdrop, BackdropCommand};
+use clock::{ClockedExt, DriftCorrector};
+use depth::{DepthMap, Layer};
+use error::ErrorScreen;
+use game::{Game, GameCommand, GameEvent};
+use launch::{Command, LaunchConfig};
+use palette::VisualMode;
 use rand::prelude::*;
 use rodio::Source;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use tempo::{PlaybackRateExt, TempoRamp};
+use theme::Theme;
 use title::TitleScreen;
 
+/// How often the debug overlay is refreshed while it's showing.
+const DEBUG_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
 fn main() {
+    attach_console();
+    // Set RUST_LOG, e.g. `RUST_LOG=chillscapes=debug`, to see what's happening
+    tracing_subscriber::fmt::init();
+
+    let args = launch::Args::from_args();
+    if let Some(Command::Validate { pack }) = &args.command {
+        let passed = validate::run(pack);
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
+    let config = match LaunchConfig::new(args) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Err(err) = config.write_last_replay() {
+        tracing::error!("Error saving replay: {:?}", err);
+    }
+    launch::initialize(config);
+
     SingleWindowApplication::run(Chillscapes::default());
 }
 
+/// The game is a Windows GUI app so that it doesn't open a console window, but
+/// that also hides `--help`, subcommand reports and logs. Write them to the
+/// console the game was started from, if there is one.
+#[cfg(windows)]
+fn attach_console() {
+    use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
 fn beats_per_second(tempo: f32) -> f32 {
     tempo / 60.
 }
@@ -24,63 +96,221 @@ fn seconds_per_beat(tempo: f32) -> f32 {
 }
 
 struct Chillscapes {
-    backdrop: Entity<Image>,
+    backdrop: Entity<Backdrop>,
+    depths: DepthMap,
     pads: &'static Loop,
+    pads_voice: Option<Voice>,
+    debug_overlay: Entity<Label>,
+    last_debug_refresh: Option<Instant>,
+    /// The visual mode the style sheet was last set for
+    visual_mode: VisualMode,
     scene_state: KludgineHandle<SceneState>,
     state: State,
 }
 
 pub struct SceneState {
-    elapsed: f32,
+    absolute_beat: f32,
     beat: f32,
     measure: usize,
+    base_tempo: f32,
     tempo: f32,
+    tempo_ramp: Option<TempoRamp>,
     beats_per_loop: usize,
+    drift: DriftCorrector,
+}
+
+impl SceneState {
+    fn new(tempo: f32, beats_per_loop: usize) -> Self {
+        Self {
+            absolute_beat: 0.,
+            beat: 0.,
+            measure: 0,
+            base_tempo: tempo,
+            tempo,
+            tempo_ramp: None,
+            beats_per_loop,
+            drift: DriftCorrector::default(),
+        }
+    }
+
+    /// Advances the song by `seconds` at the current tempo, returning true if
+    /// a new measure started. `audio_beat` is how far the audio has actually
+    /// played, which the song is gradually pulled towards.
+    fn advance(&mut self, seconds: f32, audio_beat: Option<f32>) -> bool {
+        let previous_beat = self.absolute_beat as usize;
+        self.absolute_beat += seconds * beats_per_second(self.tempo);
+        match audio_beat {
+            Some(audio_beat) => {
+                self.absolute_beat += self.drift.correct(self.absolute_beat - audio_beat, seconds);
+            }
+            None => self.drift.pause(),
+        }
+
+        if let Some(ramp) = self.tempo_ramp {
+            self.tempo = ramp.tempo_at(self.absolute_beat);
+            if ramp.is_finished(self.absolute_beat) {
+                self.tempo_ramp = None;
+            }
+        }
+        tempo::set_playback_rate(self.tempo / assets::TEMPO);
+
+        let measure = self.absolute_beat as usize / self.beats_per_loop;
+        let is_new_measure = self.measure != measure;
+        self.measure = measure;
+        if is_new_measure {
+            tracing::debug!(measure, tempo = self.tempo, "measure");
+        } else if self.absolute_beat as usize != previous_beat {
+            tracing::trace!(
+                beat = self.absolute_beat as usize,
+                tempo = self.tempo,
+                "beat"
+            );
+        }
+        self.beat = self.absolute_beat % self.beats_per_loop as f32;
+        is_new_measure
+    }
+
+    /// Gradually changes the tempo to `tempo` over the next `beats` beats.
+    pub fn ramp_tempo(&mut self, tempo: f32, beats: f32) {
+        self.tempo_ramp = Some(TempoRamp {
+            from: self.tempo,
+            to: tempo,
+            start_beat: self.absolute_beat,
+            end_beat: self.absolute_beat + beats,
+        });
+    }
 }
 
 enum State {
     TitleScreen(Entity<TitleScreen>),
     InGame(Entity<Game>),
     StartGame,
+    Error(Entity<ErrorScreen>),
 }
 
 impl Default for Chillscapes {
     fn default() -> Self {
         let pads = {
-            let mut rng = thread_rng();
+            let mut rng = random::rng();
             Loop::all()
                 .iter()
-                .filter(|p| p.kind == LoopKind::PADs)
+                // Pads with beats to hit are spawned as elements instead
+                .filter(|p| p.kind == LoopKind::PADs && p.beats.is_empty())
                 .choose(&mut rng)
                 .unwrap()
         };
 
         Self {
             pads,
+            pads_voice: None,
+            debug_overlay: Default::default(),
+            last_debug_refresh: None,
+            visual_mode: settings::current().visual_mode,
             backdrop: Default::default(),
-            scene_state: KludgineHandle::new(SceneState {
-                elapsed: 0.,
-                beat: 0.,
-                measure: 0,
-                tempo: assets::TEMPO,
-                beats_per_loop: assets::BEATS_PER_LOOP,
-            }),
+            depths: DepthMap::default(),
+            scene_state: KludgineHandle::new(SceneState::new(
+                launch::config().tempo,
+                assets::BEATS_PER_LOOP,
+            )),
             state: State::TitleScreen(Entity::default()),
         }
     }
 }
 
-impl Window for Chillscapes {}
+impl Chillscapes {
+    /// Loads everything the game needs before anything is shown.
+    async fn load() -> anyhow::Result<()> {
+        anyhow::ensure!(
+            audio::output_device().is_some(),
+            "no audio output device was found"
+        );
+        audio::watch_devices();
+        assets::load().await?;
+        Theme::load().await?;
+        Ok(())
+    }
+
+    /// Starts the pads `absolute_beat` beats into the song, looping forever
+    /// after that. The pads keep the audio clock.
+    fn play_pads(&mut self, absolute_beat: f32) {
+        // The old voice stops keeping time as soon as it's lost
+        clock::stop();
+        self.pads_voice = Voice::new();
+        if let Some(voice) = &self.pads_voice {
+            voice.set_volume(0.6);
+            let pads = self.pads;
+            let (_, beat) = pads.position(absolute_beat);
+            let loop_start = absolute_beat - beat;
+            voice.skip_then(pads.source().clone(), beat, move |sink, source, skipped| {
+                // The clock starts once the pads are ready to play
+                clock::start(
+                    loop_start + skipped,
+                    source.sample_rate(),
+                    source.channels(),
+                );
+                sink.append(source.clocked().at_playback_rate());
+                sink.append(
+                    pads.source()
+                        .clone()
+                        .repeat_infinite()
+                        .clocked()
+                        .at_playback_rate(),
+                );
+            });
+        }
+    }
+
+    async fn show_error(
+        &mut self,
+        context: &mut SceneContext,
+        error: anyhow::Error,
+    ) -> KludgineResult<()> {
+        tracing::error!("Error: {:?}", error);
+        let screen = self
+            .new_entity(context, ErrorScreen::new(&error))
+            .insert()
+            .await?;
+        self.depths.insert(
+            screen.index(),
+            Layer::Hud,
+            AbsoluteBounds::from(Surround::uniform(Dimension::from_points(0.))),
+        );
+        self.state = State::Error(screen);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Window for Chillscapes {
+    async fn close_requested(&self) -> KludgineResult<CloseResponse> {
+        // The game keeps the save data up to date in memory as it goes
+        if let Err(err) = save::write() {
+            tracing::error!("Error saving: {:?}", err);
+        }
+        Ok(CloseResponse::Close)
+    }
+}
 
 impl WindowCreator<Chillscapes> for Chillscapes {
     fn window_title() -> String {
         "Chillscapes".to_owned()
     }
+
+    fn get_window_builder() -> WindowBuilder {
+        let builder = WindowBuilder::default().with_title(Self::window_title());
+        if launch::config().fullscreen {
+            // Borderless on whichever monitor the window opens on
+            builder.with_fullscreen(Some(Fullscreen::Borderless(None)))
+        } else {
+            builder
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum Message {
     StartGame,
+    GameEvent(GameEvent),
 }
 
 #[async_trait]
@@ -97,6 +327,7 @@ impl InteractiveComponent for Chillscapes {
         match message {
             Message::StartGame => {
                 if let State::TitleScreen(title) = &self.state {
+                    self.depths.remove(title.index());
                     context.remove(title).await;
                 }
 
@@ -104,6 +335,11 @@ impl InteractiveComponent for Chillscapes {
 
                 Ok(())
             }
+            Message::GameEvent(GameEvent::ActiveKindsChanged(kinds)) => {
+                self.backdrop
+                    .send(BackdropCommand::SetActiveKinds(kinds))
+                    .await
+            }
         }
     }
 }
@@ -117,48 +353,62 @@ impl Component for Chillscapes {
             .await;
 
         context
-            .set_style_sheet(
-                Style {
-                    font_family: Some("Audiowide".to_string()),
-                    font_size: Some(16.),
-                    alignment: Some(Alignment::Center),
-                    color: Some(Color::new(1.0, 0.0, 0.9, 1.0)),
-                    ..Default::default()
-                }
-                .into(),
-            )
+            .set_style_sheet(self.visual_mode.palette().style_sheet().into())
             .await;
 
-        let backdrop_texture = include_texture!("../assets/whitevault/space/SceneOne.png")?;
-        let sprite = Sprite::single_frame(backdrop_texture).await;
+        if let Err(err) = Self::load().await {
+            return self.show_error(context, err).await;
+        }
 
+        let theme = Theme::current();
         self.backdrop = self
-            .new_entity(
-                context,
-                Image::new(sprite)
-                    .options(ImageOptions::default().scaling(ImageScaling::AspectFill)),
-            )
-            .bounds(AbsoluteBounds::from(Surround::uniform(
-                Dimension::from_points(0.),
-            )))
+            .new_entity(context, Backdrop::new(theme.backdrop.clone()))
             .insert()
             .await?;
+        self.depths.insert(
+            self.backdrop.index(),
+            Layer::Backdrop,
+            AbsoluteBounds::from(Surround::uniform(Dimension::from_points(0.))),
+        );
 
-        if let Some(device) = rodio::default_output_device() {
-            let sink = rodio::Sink::new(&device);
-            sink.append(self.pads.source.clone().repeat_infinite());
-            sink.set_volume(0.6);
-            sink.detach();
-        }
+        self.play_pads(0.);
+
+        self.debug_overlay = self
+            .new_entity(context, Label::new(""))
+            .style(Style {
+                alignment: Some(Alignment::Left),
+                color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+                font_size: Some(12.),
+                ..Default::default()
+            })
+            .insert()
+            .await?;
+        self.depths.insert(
+            self.debug_overlay.index(),
+            Layer::Hud,
+            AbsoluteBounds {
+                left: Dimension::from_points(8.),
+                top: Dimension::from_points(8.),
+                ..Default::default()
+            },
+        );
 
-        self.state = State::TitleScreen(
-            self.new_entity(context, TitleScreen::default())
+        if launch::config().skip_title {
+            self.state = State::StartGame;
+        } else {
+            let title = self
+                .new_entity(context, TitleScreen::default())
                 .callback(|_| Message::StartGame)
                 .insert()
-                .await?,
-        );
+                .await?;
+            self.depths.insert(
+                title.index(),
+                Layer::Hud,
+                AbsoluteBounds::from(Surround::uniform(Dimension::from_points(0.))),
+            );
+            self.state = State::TitleScreen(title);
+        }
 
-        // self.game = self.new_entity(context, Game::default()).insert().await?;
         Ok(())
     }
 
@@ -166,48 +416,87 @@ impl Component for Chillscapes {
         &mut self,
         _context: &mut StyledContext,
     ) -> KludgineResult<Box<dyn LayoutSolver>> {
-        let child = match &self.state {
-            State::TitleScreen(title) => title.index(),
-            State::InGame(game) => game.index(),
-            State::StartGame => return Layout::none().layout(),
-        };
-        Layout::absolute()
-            .child(
-                &self.backdrop,
-                AbsoluteBounds::from(Surround::uniform(Dimension::from_points(0.))),
-            )?
-            .child(
-                &child,
-                AbsoluteBounds::from(Surround::uniform(Dimension::from_points(0.))),
-            )?
-            .layout()
+        self.depths.layout()
     }
+    async fn keyboard_event(
+        &mut self,
+        _context: &mut Context,
+        _scancode: ScanCode,
+        key: Option<VirtualKeyCode>,
+        state: ElementState,
+    ) -> KludgineResult<()> {
+        if key == Some(VirtualKeyCode::F3) && state == ElementState::Pressed && !debug::toggle() {
+            self.debug_overlay
+                .send(LabelCommand::SetValue(String::new()))
+                .await?;
+        }
+        Ok(())
+    }
+
     async fn update(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
+        let visual_mode = settings::current().visual_mode;
+        if visual_mode != self.visual_mode {
+            self.visual_mode = visual_mode;
+            context
+                .set_style_sheet(visual_mode.palette().style_sheet().into())
+                .await;
+        }
+
+        if let State::Error(_) = &self.state {
+            return Ok(());
+        }
+
         if let State::StartGame = &self.state {
-            self.state = State::InGame(
-                self.new_entity(context, Game::new(self.scene_state.clone()))
-                    .insert()
-                    .await?,
+            let game = self
+                .new_entity(context, Game::new(self.scene_state.clone(), self.pads))
+                .callback(Message::GameEvent)
+                .insert()
+                .await?;
+            self.depths.insert(
+                game.index(),
+                Layer::Elements,
+                AbsoluteBounds::from(Surround::uniform(Dimension::from_points(0.))),
             );
+            self.state = State::InGame(game);
         }
 
         if let Some(elapsed) = context.scene().elapsed().await {
             let mut scene_data = self.scene_state.write().await;
-            scene_data.elapsed += elapsed.as_secs_f32();
-            let absolute_beat = scene_data.elapsed / seconds_per_beat(scene_data.tempo);
-            let measure = absolute_beat as usize / scene_data.beats_per_loop;
-            let is_new_measure = scene_data.measure != measure;
-            scene_data.measure = measure;
-            scene_data.beat = absolute_beat % scene_data.beats_per_loop as f32;
+            let is_new_measure = scene_data.advance(elapsed.as_secs_f32(), clock::audio_beat());
+            self.backdrop
+                .send(BackdropCommand::SetBeat(scene_data.absolute_beat))
+                .await?;
 
             if let State::InGame(game) = &self.state {
                 game.send(GameCommand::SetBeat {
                     is_new_measure,
-                    beat: scene_data.beat,
-                    measure,
+                    absolute_beat: scene_data.absolute_beat,
+                    tempo: scene_data.tempo,
                 })
                 .await?;
             }
+
+            let refresh_due = self
+                .last_debug_refresh
+                .map_or(true, |last| last.elapsed() >= DEBUG_REFRESH_INTERVAL);
+            if debug::is_enabled() && refresh_due {
+                self.last_debug_refresh = Some(Instant::now());
+                let snapshot = debug::Snapshot {
+                    absolute_beat: scene_data.absolute_beat,
+                    tempo: scene_data.tempo,
+                    frame_time: elapsed,
+                    drift: clock::audio_beat().map(|_| scene_data.drift.stats()),
+                };
+                self.debug_overlay
+                    .send(LabelCommand::SetValue(snapshot.describe()))
+                    .await?;
+            }
+        }
+
+        // Move the pads over if the output device changed or came back
+        if self.pads_voice.as_ref().map_or(true, Voice::is_stale) {
+            let absolute_beat = self.scene_state.read().await.absolute_beat;
+            self.play_pads(absolute_beat);
         }
 
         Ok(())
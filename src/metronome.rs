@@ -0,0 +1,105 @@
+use crate::{
+    assets::{self, Loop, Meter},
+    audio,
+};
+use serde::{Deserialize, Serialize};
+
+/// How much louder the click is on the first beat of each bar.
+const ACCENT: f32 = 1.6;
+
+/// What the metronome clicks along to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetronomeMode {
+    Off,
+    EveryBeat,
+    /// Only the beats of the element waiting to be locked in
+    PendingBeats,
+}
+
+impl MetronomeMode {
+    /// The mode after this one, for cycling through them with a toggle.
+    pub fn next(self) -> Self {
+        match self {
+            MetronomeMode::Off => MetronomeMode::EveryBeat,
+            MetronomeMode::EveryBeat => MetronomeMode::PendingBeats,
+            MetronomeMode::PendingBeats => MetronomeMode::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MetronomeMode::Off => "Metronome: Off",
+            MetronomeMode::EveryBeat => "Metronome: Every beat",
+            MetronomeMode::PendingBeats => "Metronome: Element beats",
+        }
+    }
+}
+
+/// Plays a click along with the music, to help players find the rhythm.
+pub struct Metronome {
+    volume: f32,
+    last_beat: Option<f32>,
+}
+
+impl Metronome {
+    pub fn new(volume: f32) -> Self {
+        Self {
+            volume,
+            last_beat: None,
+        }
+    }
+
+    /// Plays a click if a beat the metronome follows has passed since the last
+    /// call. `pending` is the loop of the element waiting to be locked in.
+    pub fn set_beat(&mut self, absolute_beat: f32, mode: MetronomeMode, pending: Option<&Loop>) {
+        let last_beat = match self.last_beat.replace(absolute_beat) {
+            Some(last_beat) if last_beat < absolute_beat => last_beat,
+            _ => return,
+        };
+
+        match mode {
+            MetronomeMode::Off => {}
+            MetronomeMode::EveryBeat => {
+                if last_beat.floor() != absolute_beat.floor() {
+                    self.play(Meter::COMMON_TIME.is_downbeat(absolute_beat.floor()));
+                }
+            }
+            MetronomeMode::PendingBeats => {
+                if let Some(pending) = pending {
+                    let length = pending.length as f32;
+                    let passed = pending
+                        .beats
+                        .iter()
+                        .find(|note| passed_between(last_beat, absolute_beat, note.beat, length));
+                    if let Some(note) = passed {
+                        self.play(pending.meter.is_downbeat(note.beat));
+                    }
+                }
+            }
+        }
+    }
+
+    fn play(&self, accented: bool) {
+        if let Some(device) = audio::output_device() {
+            let sink = rodio::Sink::new(&device);
+            sink.append(assets::click().clone());
+            if accented {
+                sink.set_volume((self.volume * ACCENT).min(1.));
+            } else {
+                sink.set_volume(self.volume);
+            }
+            sink.detach();
+        }
+    }
+}
+
+/// Returns true if `beat`, repeating every `length` beats, falls after `from`
+/// and at or before `to`.
+fn passed_between(from: f32, to: f32, beat: f32, length: f32) -> bool {
+    if length <= 0. {
+        return false;
+    }
+    let latest = ((to - beat) / length).floor() * length + beat;
+    latest > from && latest <= to
+}
@@ -0,0 +1,108 @@
+use crate::settings;
+use kludgine::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How the game is drawn, for players who have trouble telling its colors
+/// apart.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VisualMode {
+    Standard,
+    /// White text and bright, saturated accents
+    HighContrast,
+    /// Blue and orange instead of green and red, which most forms of color
+    /// blindness can still tell apart
+    Colorblind,
+}
+
+impl Default for VisualMode {
+    fn default() -> Self {
+        VisualMode::Standard
+    }
+}
+
+impl VisualMode {
+    /// The mode after this one, for cycling through them with a toggle.
+    pub fn next(self) -> Self {
+        match self {
+            VisualMode::Standard => VisualMode::HighContrast,
+            VisualMode::HighContrast => VisualMode::Colorblind,
+            VisualMode::Colorblind => VisualMode::Standard,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VisualMode::Standard => "Visuals: Standard",
+            VisualMode::HighContrast => "Visuals: High contrast",
+            VisualMode::Colorblind => "Visuals: Colorblind",
+        }
+    }
+
+    pub fn palette(self) -> Palette {
+        match self {
+            VisualMode::Standard => Palette {
+                text: Color::new(1.0, 0.0, 0.9, 1.0),
+                accent: Color::new(1.0, 0.0, 0.9, 1.0),
+                inactive: Color::new(1.0, 1.0, 1.0, 0.3),
+                success: Color::new(0.3, 1.0, 0.5, 1.0),
+                failure: Color::new(1.0, 0.3, 0.3, 1.0),
+                font_size: 16.,
+                shapes: false,
+            },
+            VisualMode::HighContrast => Palette {
+                text: Color::new(1.0, 1.0, 1.0, 1.0),
+                accent: Color::new(1.0, 0.9, 0.0, 1.0),
+                inactive: Color::new(1.0, 1.0, 1.0, 0.6),
+                success: Color::new(0.0, 1.0, 0.0, 1.0),
+                failure: Color::new(1.0, 0.0, 0.0, 1.0),
+                font_size: 18.,
+                shapes: true,
+            },
+            // From the Okabe-Ito palette
+            VisualMode::Colorblind => Palette {
+                text: Color::new(0.8, 0.47, 0.65, 1.0),
+                accent: Color::new(0.8, 0.47, 0.65, 1.0),
+                inactive: Color::new(1.0, 1.0, 1.0, 0.3),
+                success: Color::new(0.34, 0.71, 0.91, 1.0),
+                failure: Color::new(0.9, 0.62, 0.0, 1.0),
+                font_size: 16.,
+                shapes: true,
+            },
+        }
+    }
+}
+
+/// The colors used for text and feedback in a visual mode.
+#[derive(Clone, Copy, Debug)]
+pub struct Palette {
+    pub text: Color,
+    /// Highlights, like lit progress dots and the on-beat timing bar
+    pub accent: Color,
+    /// Things that are switched off or not there yet
+    pub inactive: Color,
+    pub success: Color,
+    pub failure: Color,
+    pub font_size: f32,
+    /// Whether hits and misses are marked with different shapes, so that they
+    /// can be told apart without their colors
+    pub shapes: bool,
+}
+
+impl Palette {
+    /// The style every component inherits.
+    pub fn style_sheet(&self) -> Style {
+        Style {
+            font_family: Some("Audiowide".to_string()),
+            font_size: Some(self.font_size),
+            alignment: Some(Alignment::Center),
+            color: Some(self.text),
+            ..Default::default()
+        }
+    }
+}
+
+/// The palette of the visual mode chosen in the settings.
+pub fn current() -> Palette {
+    settings::current().visual_mode.palette()
+}
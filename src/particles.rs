@@ -0,0 +1,169 @@
+use crate::random;
+use kludgine::prelude::*;
+use rand::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Short-lived sprites that fly out from a point and fade away.
+pub struct Particles {
+    sprite: Sprite,
+    sprite_size: Size,
+    particles: Vec<Particle>,
+    pending: Vec<Emission>,
+}
+
+struct Particle {
+    image: Entity<Image>,
+    origin: (f32, f32),
+    velocity: (f32, f32),
+    born: Instant,
+    lifetime: Duration,
+}
+
+impl Particle {
+    fn age(&self, now: Instant) -> f32 {
+        now.checked_duration_since(self.born)
+            .unwrap_or_default()
+            .as_secs_f32()
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.age(now) >= self.lifetime.as_secs_f32()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Emission {
+    location: (f32, f32),
+    count: usize,
+    speed: f32,
+    lifetime: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub enum ParticleCommand {
+    /// A celebratory burst of sparkles, such as when an element locks in
+    Burst(Point<Points>),
+    /// A small pulse, such as for a successful hit
+    Pulse(Point<Points>),
+}
+
+impl Particles {
+    pub fn new(sprite: Sprite) -> Self {
+        Self {
+            sprite,
+            sprite_size: Size::default(),
+            particles: Vec::default(),
+            pending: Vec::default(),
+        }
+    }
+
+    fn queue(&mut self, location: Point<Points>, count: usize, speed: f32, lifetime_millis: u64) {
+        self.pending.push(Emission {
+            location: (location.x.to_f32(), location.y.to_f32()),
+            count,
+            speed,
+            lifetime: Duration::from_millis(lifetime_millis),
+        });
+    }
+
+    async fn emit(&mut self, context: &mut SceneContext, emission: Emission) -> KludgineResult<()> {
+        let now = Instant::now();
+        let mut rng = random::rng();
+        for index in 0..emission.count {
+            let (angle, speed) = {
+                // Spread the particles evenly, with a little jitter so bursts don't look mechanical
+                let angle = std::f32::consts::PI * 2. * index as f32 / emission.count as f32
+                    + rng.gen_range(-0.3, 0.3);
+                (angle, emission.speed * rng.gen_range(0.6, 1.2))
+            };
+
+            let image = self
+                .new_entity(context, Image::new(self.sprite.clone()))
+                .insert()
+                .await?;
+
+            self.particles.push(Particle {
+                image,
+                origin: emission.location,
+                velocity: (angle.cos() * speed, angle.sin() * speed),
+                born: now,
+                lifetime: emission.lifetime,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Component for Particles {
+    async fn initialize(&mut self, _context: &mut SceneContext) -> KludgineResult<()> {
+        if let Some(size) = self.sprite.size().await {
+            self.sprite_size = Size::new(size.width as f32, size.height as f32);
+        }
+        Ok(())
+    }
+
+    async fn layout(
+        &mut self,
+        _context: &mut StyledContext,
+    ) -> KludgineResult<Box<dyn LayoutSolver>> {
+        let now = Instant::now();
+        let mut layout = Layout::absolute();
+        for particle in &self.particles {
+            let age = particle.age(now);
+            let left = particle.origin.0 + particle.velocity.0 * age - self.sprite_size.width / 2.;
+            let top = particle.origin.1 + particle.velocity.1 * age - self.sprite_size.height / 2.;
+            layout = layout.child(
+                &particle.image,
+                AbsoluteBounds {
+                    left: Dimension::from_points(left),
+                    top: Dimension::from_points(top),
+                    ..Default::default()
+                },
+            )?;
+        }
+        layout.layout()
+    }
+
+    async fn update(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
+        for emission in std::mem::take(&mut self.pending) {
+            self.emit(context, emission).await?;
+        }
+
+        let now = Instant::now();
+        for particle in self.particles.iter().filter(|p| p.is_expired(now)) {
+            context.remove(&particle.image).await;
+        }
+        self.particles.retain(|p| !p.is_expired(now));
+
+        for particle in &self.particles {
+            let remaining = 1. - particle.age(now) / particle.lifetime.as_secs_f32();
+            particle
+                .image
+                .send(ImageCommand::SetAlpha(remaining))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InteractiveComponent for Particles {
+    type Message = ();
+    type Input = ParticleCommand;
+    type Output = ();
+
+    async fn receive_input(
+        &mut self,
+        _context: &mut Context,
+        command: Self::Input,
+    ) -> KludgineResult<()> {
+        match command {
+            ParticleCommand::Burst(location) => self.queue(location, 16, 120., 900),
+            ParticleCommand::Pulse(location) => self.queue(location, 4, 40., 300),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+use crate::launch;
+use once_cell::sync::Lazy;
+use rand::{rngs::StdRng, SeedableRng};
+use std::sync::Mutex;
+
+static SEEDER: Lazy<Mutex<StdRng>> =
+    Lazy::new(|| Mutex::new(StdRng::seed_from_u64(launch::config().seed)));
+
+/// Returns a random number generator for gameplay choices. They're all derived
+/// from the launch seed, so the same seed plays out the same way.
+pub fn rng() -> StdRng {
+    StdRng::from_rng(&mut *SEEDER.lock().unwrap()).unwrap()
+}
@@ -0,0 +1,337 @@
+use crate::{metronome::MetronomeMode, palette::VisualMode};
+use anyhow::Context as _;
+use directories::ProjectDirs;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+    time::Duration,
+};
+
+/// The version of the save format written by this build.
+const CURRENT_VERSION: u64 = 4;
+
+type Migration = fn(Value) -> Value;
+
+/// Upgrades older save files one version at a time. The first entry upgrades
+/// version 1 to version 2, and so on. Add an entry here whenever the format
+/// changes, and bump `CURRENT_VERSION`.
+const MIGRATIONS: &[Migration] = &[add_audio_device, add_visual_mode, add_hud_and_metronome];
+
+/// Version 2 remembers the chosen audio output device.
+fn add_audio_device(mut value: Value) -> Value {
+    value["audio_device"] = Value::Null;
+    value
+}
+
+/// Version 3 remembers the chosen visual mode.
+fn add_visual_mode(mut value: Value) -> Value {
+    value["visual_mode"] = "standard".into();
+    value
+}
+
+/// Version 4 remembers whether the HUD is shown and what the metronome plays.
+fn add_hud_and_metronome(mut value: Value) -> Value {
+    value["show_hud"] = true.into();
+    value["metronome"] = "off".into();
+    value
+}
+
+const DEFAULT_PROFILE: &str = "Player";
+
+/// Everything remembered between runs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u64,
+    /// Which of the profiles is playing
+    pub active_profile: usize,
+    pub profiles: Vec<Profile>,
+    /// The name of the output device to play on, or `None` for the system
+    /// default
+    pub audio_device: Option<String>,
+    pub visual_mode: VisualMode,
+    pub show_hud: bool,
+    pub metronome: MetronomeMode,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub best_scores: Vec<BestScore>,
+    pub play_time: Duration,
+    pub lock_ins: u64,
+}
+
+/// The best score reached with a theme at a difficulty.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BestScore {
+    pub theme: String,
+    pub difficulty: String,
+    pub score: u64,
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            active_profile: 0,
+            profiles: vec![Profile::new(DEFAULT_PROFILE)],
+            audio_device: None,
+            visual_mode: VisualMode::default(),
+            show_hud: true,
+            metronome: MetronomeMode::Off,
+        }
+    }
+}
+
+impl Profile {
+    pub fn new<S: ToString>(name: S) -> Self {
+        Self {
+            name: name.to_string(),
+            best_scores: Vec::default(),
+            play_time: Duration::default(),
+            lock_ins: 0,
+        }
+    }
+
+    pub fn best_score(&self, theme: &str, difficulty: &str) -> Option<u64> {
+        self.best_scores
+            .iter()
+            .find(|best| best.theme == theme && best.difficulty == difficulty)
+            .map(|best| best.score)
+    }
+
+    /// Records a score, returning true if it's a new best.
+    pub fn record_score(&mut self, theme: &str, difficulty: &str, score: u64) -> bool {
+        match self
+            .best_scores
+            .iter_mut()
+            .find(|best| best.theme == theme && best.difficulty == difficulty)
+        {
+            Some(best) if best.score >= score => false,
+            Some(best) => {
+                best.score = score;
+                true
+            }
+            None => {
+                self.best_scores.push(BestScore {
+                    theme: theme.to_string(),
+                    difficulty: difficulty.to_string(),
+                    score,
+                });
+                true
+            }
+        }
+    }
+}
+
+impl SaveData {
+    /// The profile that's playing, creating one if there are none.
+    pub fn profile_mut(&mut self) -> &mut Profile {
+        if self.profiles.is_empty() {
+            self.profiles.push(Profile::new(DEFAULT_PROFILE));
+        }
+        self.active_profile = self.active_profile.min(self.profiles.len() - 1);
+        &mut self.profiles[self.active_profile]
+    }
+
+    /// Parses a save file of any version up to the current one.
+    fn parse(json: &str) -> anyhow::Result<Self> {
+        let mut value = serde_json::from_str::<Value>(json)?;
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .context("save data has no version")?;
+        if version == 0 || version > CURRENT_VERSION {
+            anyhow::bail!("unsupported save data version {}", version);
+        }
+
+        for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+            value = migration(value);
+        }
+        value["version"] = CURRENT_VERSION.into();
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Loads save data from `path`, or the defaults if nothing has been saved.
+    fn load_from(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let json = fs::read_to_string(path)?;
+        Self::parse(&json).with_context(|| format!("reading {}", path.display()))
+    }
+
+    /// Writes to a temporary file next to `path` and then moves it into place,
+    /// so a crash partway through never leaves a half-written save behind.
+    fn write_to(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let temporary = path.with_extension("json.tmp");
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+}
+
+/// Where everything the game writes is kept.
+pub fn data_dir() -> Option<PathBuf> {
+    ProjectDirs::from("com", "khonsulabs", "chillscapes").map(|dirs| dirs.data_dir().to_owned())
+}
+
+fn save_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("save.json"))
+}
+
+static SAVE_DATA: Lazy<RwLock<SaveData>> = Lazy::new(|| {
+    let path = match save_path() {
+        Some(path) => path,
+        None => return RwLock::new(SaveData::default()),
+    };
+
+    match SaveData::load_from(&path) {
+        Ok(data) => RwLock::new(data),
+        Err(err) => {
+            // Keep the unreadable save around rather than overwriting it
+            tracing::error!("Error loading save data: {:?}", err);
+            let _ = fs::rename(&path, path.with_extension("json.bad"));
+            RwLock::new(SaveData::default())
+        }
+    }
+});
+
+/// Returns a copy of the current save data.
+pub fn current() -> SaveData {
+    SAVE_DATA.read().unwrap().clone()
+}
+
+/// Changes the save data in memory, returning the updated copy. Call `write`
+/// to persist it.
+pub fn update<F: FnOnce(&mut SaveData)>(change: F) -> SaveData {
+    let mut data = SAVE_DATA.write().unwrap();
+    change(&mut data);
+    data.clone()
+}
+
+/// Held while writing, so that writes never share the temporary file.
+static WRITING: Lazy<Mutex<()>> = Lazy::new(Default::default);
+
+/// Writes the save data to the user's data directory.
+pub fn write() -> anyhow::Result<()> {
+    let path = save_path().context("no user data directory")?;
+    let _writing = WRITING.lock().unwrap();
+    current().write_to(&path)
+}
+
+/// Writes the save data on a background thread, so that saving during play
+/// doesn't hold up the frame.
+pub fn write_in_background() {
+    std::thread::spawn(|| {
+        if let Err(err) = write() {
+            tracing::error!("Error saving: {:?}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrations_cover_every_version() {
+        assert_eq!(MIGRATIONS.len() as u64, CURRENT_VERSION - 1);
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let mut data = SaveData::default();
+        let profile = data.profile_mut();
+        profile.lock_ins = 3;
+        profile.play_time = Duration::from_secs(90);
+        profile.record_score("space", "normal", 1200);
+
+        let path = std::env::temp_dir().join(format!("chillscapes-{}.json", std::process::id()));
+        data.write_to(&path).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+        assert_eq!(SaveData::load_from(&path).unwrap(), data);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_files_load_the_defaults() {
+        let path = std::env::temp_dir().join("chillscapes-does-not-exist.json");
+        assert_eq!(SaveData::load_from(&path).unwrap(), SaveData::default());
+    }
+
+    #[test]
+    fn unsupported_versions_are_rejected() {
+        let newer = format!(
+            r#"{{"version": {}, "active_profile": 0, "profiles": []}}"#,
+            CURRENT_VERSION + 1
+        );
+        assert!(SaveData::parse(&newer).is_err());
+        assert!(SaveData::parse(r#"{"active_profile": 0, "profiles": []}"#).is_err());
+    }
+
+    #[test]
+    fn version_1_saves_are_migrated() {
+        let data = SaveData::parse(
+            r#"{"version": 1, "active_profile": 0, "profiles": [
+                {"name": "test", "best_scores": [], "play_time": {"secs": 5, "nanos": 0}, "lock_ins": 2}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(data.version, CURRENT_VERSION);
+        assert_eq!(data.audio_device, None);
+        assert_eq!(data.visual_mode, VisualMode::Standard);
+        assert!(data.show_hud);
+        assert_eq!(data.metronome, MetronomeMode::Off);
+        assert_eq!(data.profiles[0].lock_ins, 2);
+    }
+
+    #[test]
+    fn version_3_saves_keep_their_settings() {
+        let data = SaveData::parse(
+            r#"{"version": 3, "active_profile": 0, "profiles": [],
+                "audio_device": "Headphones", "visual_mode": "colorblind"}"#,
+        )
+        .unwrap();
+        assert_eq!(data.audio_device.as_deref(), Some("Headphones"));
+        assert_eq!(data.visual_mode, VisualMode::Colorblind);
+        assert!(data.show_hud);
+        assert_eq!(data.metronome, MetronomeMode::Off);
+    }
+
+    #[test]
+    fn metronome_modes_round_trip() {
+        let mut data = SaveData::default();
+        data.show_hud = false;
+        data.metronome = MetronomeMode::PendingBeats;
+        let json = serde_json::to_string(&data).unwrap();
+        assert!(json.contains(r#""metronome":"pending_beats""#));
+        assert_eq!(SaveData::parse(&json).unwrap(), data);
+    }
+
+    #[test]
+    fn only_better_scores_are_recorded() {
+        let mut profile = Profile::new("test");
+        assert!(profile.record_score("space", "normal", 100));
+        assert!(!profile.record_score("space", "normal", 50));
+        assert!(profile.record_score("space", "normal", 150));
+        assert!(profile.record_score("space", "hard", 10));
+        assert_eq!(profile.best_score("space", "normal"), Some(150));
+        assert_eq!(profile.best_score("space", "hard"), Some(10));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{metronome::MetronomeMode, palette::VisualMode, save};
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+/// Player preferences that apply across games.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub show_hud: bool,
+    pub metronome: MetronomeMode,
+    /// The name of the output device to play on, or `None` for the system
+    /// default
+    pub audio_device: Option<String>,
+    pub visual_mode: VisualMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            show_hud: true,
+            metronome: MetronomeMode::Off,
+            audio_device: None,
+            visual_mode: VisualMode::default(),
+        }
+    }
+}
+
+static SETTINGS: Lazy<RwLock<Settings>> = Lazy::new(|| {
+    let saved = save::current();
+    RwLock::new(Settings {
+        show_hud: saved.show_hud,
+        metronome: saved.metronome,
+        audio_device: saved.audio_device,
+        visual_mode: saved.visual_mode,
+    })
+});
+
+/// Returns a copy of the current settings.
+pub fn current() -> Settings {
+    SETTINGS.read().unwrap().clone()
+}
+
+/// Changes the current settings, returning the updated copy.
+pub fn update<F: FnOnce(&mut Settings)>(change: F) -> Settings {
+    let mut settings = SETTINGS.write().unwrap();
+    change(&mut settings);
+    settings.clone()
+}
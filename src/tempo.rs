@@ -0,0 +1,118 @@
+use once_cell::sync::Lazy;
+use rodio::{Sample, Source};
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+/// The playback rate shared by every playing loop, stored as the bits of an f32
+/// so that the audio thread can read it without locking.
+static PLAYBACK_RATE: Lazy<AtomicU32> = Lazy::new(|| AtomicU32::new(1f32.to_bits()));
+
+/// The largest number of samples played before a rate change is picked up.
+const MAX_FRAME_LEN: usize = 1024;
+
+pub fn playback_rate() -> f32 {
+    f32::from_bits(PLAYBACK_RATE.load(Ordering::Relaxed))
+}
+
+/// Sets how fast loops play relative to the tempo they were recorded at.
+pub fn set_playback_rate(rate: f32) {
+    PLAYBACK_RATE.store(rate.to_bits(), Ordering::Relaxed);
+}
+
+/// A gradual change in tempo between two points in the song.
+#[derive(Clone, Copy, Debug)]
+pub struct TempoRamp {
+    pub from: f32,
+    pub to: f32,
+    pub start_beat: f32,
+    pub end_beat: f32,
+}
+
+impl TempoRamp {
+    pub fn tempo_at(&self, absolute_beat: f32) -> f32 {
+        let length = self.end_beat - self.start_beat;
+        if length <= 0. || absolute_beat >= self.end_beat {
+            self.to
+        } else if absolute_beat <= self.start_beat {
+            self.from
+        } else {
+            let progress = (absolute_beat - self.start_beat) / length;
+            self.from + (self.to - self.from) * progress
+        }
+    }
+
+    pub fn is_finished(&self, absolute_beat: f32) -> bool {
+        absolute_beat >= self.end_beat
+    }
+}
+
+/// Resamples a source so that it follows the current playback rate. Like
+/// speeding up or slowing down a record, this changes the pitch along with the
+/// tempo, so tempo changes are best kept small.
+pub struct PlaybackRate<I> {
+    input: I,
+}
+
+pub trait PlaybackRateExt: Source + Sized
+where
+    Self::Item: Sample,
+{
+    fn at_playback_rate(self) -> PlaybackRate<Self> {
+        PlaybackRate { input: self }
+    }
+}
+
+impl<I> PlaybackRateExt for I
+where
+    I: Source,
+    I::Item: Sample,
+{
+}
+
+impl<I> Iterator for PlaybackRate<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.input.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> Source for PlaybackRate<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        // The mixer only checks the sample rate between frames, so keep frames
+        // short enough that tempo changes are heard right away.
+        let frame_len = MAX_FRAME_LEN - MAX_FRAME_LEN % self.input.channels().max(1) as usize;
+        Some(
+            self.input
+                .current_frame_len()
+                .map(|len| len.min(frame_len))
+                .unwrap_or(frame_len),
+        )
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        (self.input.sample_rate() as f32 * playback_rate()) as u32
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{
+    assets::{DrumPart, Loader, LoopKind},
+    launch,
+};
+use anyhow::Context as _;
+use kludgine::prelude::*;
+use once_cell::sync::OnceCell;
+
+static CURRENT: OnceCell<Theme> = OnceCell::new();
+
+/// The art that gives a scene its look, separate from the gameplay.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub name: &'static str,
+    /// Sprite used for the sparkles and pulses emitted by elements, and for
+    /// the marker that orbits them ahead of their beats
+    pub particle: Sprite,
+    /// Shown where the player clicks, with "Yes" and "No" tags for hits and
+    /// misses
+    pub clicks: Sprite,
+    /// Played over an element when it locks in
+    pub success: Sprite,
+    /// Played over an element when it loses all of its progress
+    pub fail: Sprite,
+    pub backdrop: BackdropStyle,
+}
+
+/// How the backdrop reacts to the music.
+#[derive(Clone, Debug)]
+pub struct BackdropStyle {
+    pub image: Sprite,
+    pub star: Sprite,
+    /// Layers of stars, from furthest to nearest
+    pub star_layers: Vec<StarLayer>,
+    /// How much brighter the backdrop gets on each kick, from 0 to 1
+    pub pulse_strength: f32,
+    /// Colors washed over the backdrop while each kind of loop is playing
+    pub kind_colors: Vec<(LoopKind, [f32; 3])>,
+    /// Opacity of the color wash when any loops are playing
+    pub tint_alpha: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct StarLayer {
+    pub count: usize,
+    /// How fast the layer drifts, in screen widths per second
+    pub speed: f32,
+    pub alpha: f32,
+}
+
+impl BackdropStyle {
+    pub fn color_for(&self, kind: &LoopKind) -> Option<[f32; 3]> {
+        self.kind_colors
+            .iter()
+            .find(|(k, _)| k == kind)
+            .map(|(_, color)| *color)
+    }
+}
+
+impl Theme {
+    pub const SPACE_NAME: &'static str = "space";
+
+    /// Loads the theme chosen when the game was launched.
+    pub async fn load() -> anyhow::Result<&'static Theme> {
+        if let Some(theme) = CURRENT.get() {
+            return Ok(theme);
+        }
+
+        let mut loader = Loader::default();
+        let theme = Self::load_named(&launch::config().theme, &mut loader).await;
+        loader.finish()?;
+        let theme = theme.context("loading the theme")?;
+        Ok(CURRENT.get_or_init(|| theme))
+    }
+
+    /// Loads the theme called `name`, recording what failed in `loader`.
+    pub async fn load_named(name: &str, loader: &mut Loader) -> Option<Theme> {
+        match name {
+            Self::SPACE_NAME => Self::space(loader).await,
+            other => {
+                loader
+                    .errors
+                    .push(anyhow::anyhow!("unknown theme {:?}", other));
+                None
+            }
+        }
+    }
+
+    /// The theme chosen when the game was launched.
+    pub fn current() -> &'static Theme {
+        CURRENT
+            .get()
+            .expect("Theme::load runs before the game starts")
+    }
+
+    async fn space(loader: &mut Loader) -> Option<Theme> {
+        let star = loader.load(
+            "star",
+            include_aseprite_sprite!("../assets/ecton/star").await,
+        );
+        let clicks = loader.load(
+            "clicks",
+            include_aseprite_sprite!("../assets/whitevault/space/clicks").await,
+        );
+        let success = loader.load(
+            "success",
+            include_aseprite_sprite!("../assets/whitevault/space/success").await,
+        );
+        let fail = loader.load(
+            "fail",
+            include_aseprite_sprite!("../assets/whitevault/space/fail").await,
+        );
+        let backdrop_texture = loader.load(
+            "SceneOne",
+            include_texture!("../assets/whitevault/space/SceneOne.png"),
+        );
+        let (star, clicks, success, fail) = (star?, clicks?, success?, fail?);
+        let image = Sprite::single_frame(backdrop_texture?).await;
+
+        Some(Theme {
+            name: Self::SPACE_NAME,
+            particle: star.clone(),
+            clicks,
+            success,
+            fail,
+            backdrop: BackdropStyle {
+                image,
+                star,
+                star_layers: vec![
+                    StarLayer {
+                        count: 12,
+                        speed: 0.002,
+                        alpha: 0.2,
+                    },
+                    StarLayer {
+                        count: 8,
+                        speed: 0.005,
+                        alpha: 0.35,
+                    },
+                    StarLayer {
+                        count: 4,
+                        speed: 0.01,
+                        alpha: 0.5,
+                    },
+                ],
+                pulse_strength: 0.3,
+                kind_colors: vec![
+                    (LoopKind::ARPs, [0.3, 0.8, 1.0]),
+                    (LoopKind::Bass, [0.5, 0.2, 0.9]),
+                    (LoopKind::Drums(DrumPart::KickSnare), [1.0, 0.4, 0.3]),
+                    (LoopKind::Drums(DrumPart::Hats), [1.0, 0.6, 0.4]),
+                    (LoopKind::Drums(DrumPart::Percussion), [0.9, 0.3, 0.5]),
+                    (LoopKind::Piano, [1.0, 0.8, 0.4]),
+                    (LoopKind::Leads, [1.0, 0.0, 0.9]),
+                ],
+                tint_alpha: 0.12,
+            },
+        })
+    }
+}
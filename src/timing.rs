@@ -0,0 +1,60 @@
+use std::collections::VecDeque;
+
+/// How many of the most recent offsets are kept.
+const HISTORY_LENGTH: usize = 64;
+/// How wide each histogram bucket is, in milliseconds.
+const BUCKET_MILLIS: i64 = 25;
+/// How many buckets there are on either side of the beat.
+const BUCKETS_PER_SIDE: usize = 6;
+pub const BUCKETS: usize = BUCKETS_PER_SIDE * 2 + 1;
+/// Offsets this close to the beat aren't called early or late, in milliseconds.
+const ON_TIME_MILLIS: i64 = 20;
+
+/// Describes an offset from the beat the way it's shown to the player.
+/// Negative offsets are early, positive offsets are late.
+pub fn describe_offset(offset_millis: i64) -> String {
+    if offset_millis.abs() <= ON_TIME_MILLIS {
+        "on time".to_string()
+    } else if offset_millis < 0 {
+        format!("early {}ms", -offset_millis)
+    } else {
+        format!("late {}ms", offset_millis)
+    }
+}
+
+/// A rolling record of how far from the beat recent clicks landed, so players
+/// can see whether they tend to be early or late.
+#[derive(Clone, Debug, Default)]
+pub struct TimingHistory {
+    offsets: VecDeque<i64>,
+}
+
+impl TimingHistory {
+    pub fn record(&mut self, offset_millis: i64) {
+        if self.offsets.len() >= HISTORY_LENGTH {
+            self.offsets.pop_front();
+        }
+        self.offsets.push_back(offset_millis);
+    }
+
+    /// The average offset, if anything has been recorded.
+    pub fn mean(&self) -> Option<i64> {
+        if self.offsets.is_empty() {
+            None
+        } else {
+            Some(self.offsets.iter().sum::<i64>() / self.offsets.len() as i64)
+        }
+    }
+
+    /// Counts the offsets in each bucket, from earliest to latest. Offsets
+    /// beyond the outermost buckets are counted in them.
+    pub fn histogram(&self) -> [usize; BUCKETS] {
+        let mut buckets = [0; BUCKETS];
+        for offset in &self.offsets {
+            let bucket =
+                (offset + BUCKET_MILLIS / 2).div_euclid(BUCKET_MILLIS) + BUCKETS_PER_SIDE as i64;
+            buckets[bucket.max(0).min(BUCKETS as i64 - 1) as usize] += 1;
+        }
+        buckets
+    }
+}
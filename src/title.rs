@@ -1,3 +1,4 @@
+use crate::{audio, palette::VisualMode, save, settings};
 use kludgine::prelude::*;
 
 #[derive(Default)]
@@ -7,6 +8,11 @@ pub struct TitleScreen {
     music_by: Entity<Label>,
     art_by: Entity<Label>,
     code_by: Entity<Label>,
+    hud_toggle: Entity<Label>,
+    audio_device_toggle: Entity<Label>,
+    visual_mode_toggle: Entity<Label>,
+    /// The visual mode the start button was colored for
+    start_button_mode: VisualMode,
 }
 
 #[derive(Clone, Debug)]
@@ -20,6 +26,38 @@ pub enum Message {
     ArtByClicked,
     CodeByClicked,
     StartClicked,
+    HudToggleClicked,
+    AudioDeviceToggleClicked,
+    VisualModeToggleClicked,
+}
+
+fn hud_toggle_label(show_hud: bool) -> String {
+    if show_hud {
+        "HUD: On".to_string()
+    } else {
+        "HUD: Off".to_string()
+    }
+}
+
+fn audio_device_label(audio_device: Option<&str>) -> String {
+    format!("Audio: {}", audio_device.unwrap_or("System default"))
+}
+
+impl TitleScreen {
+    async fn insert_start_button(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
+        self.start_button_mode = settings::current().visual_mode;
+        self.start_button = self
+            .new_entity(context, Button::new("Start"))
+            .callback(|_| Message::StartClicked)
+            .style(Style {
+                color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+                background_color: Some(self.start_button_mode.palette().accent),
+                ..Default::default()
+            })
+            .insert()
+            .await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -64,17 +102,55 @@ impl Component for TitleScreen {
             .insert()
             .await?;
 
-        self.start_button = self
-            .new_entity(context, Button::new("Start"))
-            .callback(|_| Message::StartClicked)
-            .style(Style {
+        self.hud_toggle = self
+            .new_entity(
+                context,
+                Label::new(&hud_toggle_label(settings::current().show_hud)),
+            )
+            .callback(|_| Message::HudToggleClicked)
+            .hover(Style {
+                color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+                ..Default::default()
+            })
+            .insert()
+            .await?;
+
+        self.audio_device_toggle = self
+            .new_entity(
+                context,
+                Label::new(&audio_device_label(
+                    settings::current().audio_device.as_deref(),
+                )),
+            )
+            .callback(|_| Message::AudioDeviceToggleClicked)
+            .hover(Style {
+                color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+                ..Default::default()
+            })
+            .insert()
+            .await?;
+
+        self.visual_mode_toggle = self
+            .new_entity(context, Label::new(settings::current().visual_mode.label()))
+            .callback(|_| Message::VisualModeToggleClicked)
+            .hover(Style {
                 color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
-                background_color: Some(Color::new(1.0, 0.0, 0.9, 1.0)),
                 ..Default::default()
             })
             .insert()
             .await?;
 
+        self.insert_start_button(context).await?;
+
+        Ok(())
+    }
+
+    async fn update(&mut self, context: &mut SceneContext) -> KludgineResult<()> {
+        // Styles are fixed once inserted, so recolor the button by replacing it
+        if settings::current().visual_mode != self.start_button_mode {
+            context.remove(&self.start_button).await;
+            self.insert_start_button(context).await?;
+        }
         Ok(())
     }
 
@@ -99,6 +175,27 @@ impl Component for TitleScreen {
                     ..Default::default()
                 },
             )?
+            .child(
+                &self.hud_toggle,
+                AbsoluteBounds {
+                    top: Dimension::from_points(window_size.height / 3. * 2. + 48.),
+                    ..Default::default()
+                },
+            )?
+            .child(
+                &self.audio_device_toggle,
+                AbsoluteBounds {
+                    top: Dimension::from_points(window_size.height / 3. * 2. + 80.),
+                    ..Default::default()
+                },
+            )?
+            .child(
+                &self.visual_mode_toggle,
+                AbsoluteBounds {
+                    top: Dimension::from_points(window_size.height / 3. * 2. + 112.),
+                    ..Default::default()
+                },
+            )?
             .child(
                 &self.code_by,
                 AbsoluteBounds {
@@ -150,6 +247,45 @@ impl InteractiveComponent for TitleScreen {
             Message::StartClicked => {
                 self.callback(context, TitleScreenEvent::StartGame).await;
             }
+            Message::HudToggleClicked => {
+                let settings = settings::update(|settings| settings.show_hud = !settings.show_hud);
+                save::update(|data| data.show_hud = settings.show_hud);
+                if let Err(err) = save::write() {
+                    tracing::error!("Error saving: {:?}", err);
+                }
+                self.hud_toggle
+                    .send(LabelCommand::SetValue(hud_toggle_label(settings.show_hud)))
+                    .await?;
+            }
+            Message::AudioDeviceToggleClicked => {
+                let current = settings::current().audio_device;
+                let next = audio::next_device(current.as_deref(), &audio::device_names());
+                settings::update(|settings| settings.audio_device = next.clone());
+                save::update(|data| data.audio_device = next.clone());
+                if let Err(err) = save::write() {
+                    tracing::error!("Error saving: {:?}", err);
+                }
+                // Anything playing moves over to the new device right away
+                audio::check_device();
+
+                self.audio_device_toggle
+                    .send(LabelCommand::SetValue(audio_device_label(next.as_deref())))
+                    .await?;
+            }
+            Message::VisualModeToggleClicked => {
+                let settings =
+                    settings::update(|settings| settings.visual_mode = settings.visual_mode.next());
+                save::update(|data| data.visual_mode = settings.visual_mode);
+                if let Err(err) = save::write() {
+                    tracing::error!("Error saving: {:?}", err);
+                }
+
+                self.visual_mode_toggle
+                    .send(LabelCommand::SetValue(
+                        settings.visual_mode.label().to_string(),
+                    ))
+                    .await?;
+            }
         }
         Ok(())
     }
@@ -0,0 +1,104 @@
+use crate::judge::Miss;
+
+/// How many misses of the same kind in a row before offering a hint.
+const MISSES_BEFORE_HINT: usize = 3;
+/// How many elements need to be locked in before the tutorial goes away.
+const LOCK_INS_TO_FINISH: usize = 2;
+
+const FIRST_ELEMENT_HELP: &str =
+    "Click on the new element each time it lights up.\nThe click track will help you find the beat.";
+const PLAYING_HELP: &str = "Nice! Each new element has its own rhythm.\nRelax and enjoy the music.";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+    /// Guiding the player through their first element, with a metronome
+    FirstElement,
+    Playing,
+    Finished,
+}
+
+/// Walks new players through their first elements, offering hints when they
+/// keep missing in the same way.
+pub struct Tutorial {
+    stage: Stage,
+    lock_ins: usize,
+    recent_misses: Vec<Miss>,
+}
+
+impl Default for Tutorial {
+    fn default() -> Self {
+        Self {
+            stage: Stage::FirstElement,
+            lock_ins: 0,
+            recent_misses: Vec::default(),
+        }
+    }
+}
+
+impl Tutorial {
+    /// A tutorial that's already finished, for players who don't want one.
+    pub fn skipped() -> Self {
+        Self {
+            stage: Stage::Finished,
+            ..Self::default()
+        }
+    }
+
+    /// The help text that should currently be shown, if any.
+    pub fn help_text(&self) -> Option<&'static str> {
+        match self.stage {
+            Stage::FirstElement => Some(FIRST_ELEMENT_HELP),
+            Stage::Playing => Some(PLAYING_HELP),
+            Stage::Finished => None,
+        }
+    }
+
+    pub fn metronome_enabled(&self) -> bool {
+        self.stage == Stage::FirstElement
+    }
+
+    pub fn record_success(&mut self) {
+        self.recent_misses.clear();
+    }
+
+    /// Records a miss, returning a hint if the player keeps missing the same way.
+    pub fn record_miss(&mut self, miss: Miss) -> Option<&'static str> {
+        if self.stage == Stage::Finished {
+            return None;
+        }
+
+        if self.recent_misses.last() != Some(&miss) {
+            self.recent_misses.clear();
+        }
+        self.recent_misses.push(miss);
+
+        if self.recent_misses.len() >= MISSES_BEFORE_HINT {
+            self.recent_misses.clear();
+            Some(Self::hint_for(miss))
+        } else {
+            None
+        }
+    }
+
+    /// Records an element locking in, returning true if the help text changed.
+    pub fn record_lock_in(&mut self) -> bool {
+        self.lock_ins += 1;
+        let stage = if self.lock_ins >= LOCK_INS_TO_FINISH {
+            Stage::Finished
+        } else {
+            Stage::Playing
+        };
+        let changed = stage != self.stage;
+        self.stage = stage;
+        changed
+    }
+
+    fn hint_for(miss: Miss) -> &'static str {
+        match miss {
+            Miss::Early => "You're clicking early.\nWait for the element to light up.",
+            Miss::Late => "You're clicking late.\nTry clicking right as you hear the sound.",
+            Miss::Skipped => "Don't forget to click!\nEvery time the element lights up is a beat.",
+            Miss::WrongTarget => "Drag onto another element, or back onto this one if it's alone.",
+        }
+    }
+}
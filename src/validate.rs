@@ -0,0 +1,144 @@
+use crate::{
+    assets::{self, Animation, Loader, Loop, TEMPO},
+    feedback, seconds_per_beat,
+    theme::Theme,
+};
+use kludgine::prelude::*;
+use rodio::Source;
+use std::time::Duration;
+
+/// How far a loop's audio can be from its declared length, in seconds.
+const LENGTH_TOLERANCE_SECONDS: f32 = 0.02;
+
+/// The problems found while validating a pack.
+#[derive(Default)]
+struct Report {
+    checked: usize,
+    errors: Vec<String>,
+}
+
+impl Report {
+    fn error<S: ToString>(&mut self, asset: &str, message: S) {
+        self.errors
+            .push(format!("{}: {}", asset, message.to_string()));
+    }
+}
+
+/// Checks every asset in `pack`, printing a report. Returns true if no
+/// problems were found.
+pub fn run(pack: &str) -> bool {
+    let mut report = Report::default();
+    futures::executor::block_on(check_pack(&mut report, pack));
+
+    for error in &report.errors {
+        println!("error: {}", error);
+    }
+    println!(
+        "{} assets checked, {} errors",
+        report.checked,
+        report.errors.len()
+    );
+    report.errors.is_empty()
+}
+
+/// Loads the pack the same way the game does, then checks what loaded.
+async fn check_pack(report: &mut Report, pack: &str) {
+    let mut loader = Loader::default();
+    assets::load_into(&mut loader).await;
+    let theme = Theme::load_named(pack, &mut loader).await;
+    report.checked += loader.checked;
+    report
+        .errors
+        .extend(loader.errors.iter().map(|err| format!("{:#}", err)));
+
+    for audio_loop in Loop::all() {
+        check_loop(report, audio_loop);
+    }
+    for animation in Animation::all() {
+        let name = format!("animation {}", animation.id);
+        check_sprite(report, &name, &animation.sprite, &[]).await;
+    }
+    if let Some(theme) = theme {
+        check_sprite(report, "particle", &theme.particle, &[]).await;
+        check_sprite(report, "clicks", &theme.clicks, &["Yes", "No"]).await;
+        check_sprite(report, "success", &theme.success, &[]).await;
+        check_sprite(report, "fail", &theme.fail, &[]).await;
+        // Feedback plays for as long as these sprites' frames take
+        for (name, sprite) in &[("success", &theme.success), ("fail", &theme.fail)] {
+            if feedback::play_length(sprite).await == Duration::default() {
+                report.error(name, "sprite has no frame timings");
+            }
+        }
+        check_sprite(report, "backdrop", &theme.backdrop.image, &[]).await;
+    }
+}
+
+fn check_loop(report: &mut Report, audio_loop: &Loop) {
+    let name = audio_loop.name;
+
+    // Audio that can't be decoded has already been reported by the loader
+    if let Ok(decoder) = Loop::decode(audio_loop.audio) {
+        let channels = decoder.channels() as f32;
+        let sample_rate = decoder.sample_rate() as f32;
+        let seconds = decoder.count() as f32 / channels / sample_rate;
+        // Short loops can be cut from a recording that repeats them
+        let expected = audio_loop.length as f32 * seconds_per_beat(TEMPO);
+        let repetitions = (seconds / expected).round().max(1.);
+        if (seconds - expected * repetitions).abs() > LENGTH_TOLERANCE_SECONDS {
+            report.error(
+                name,
+                format!(
+                    "audio is {:.3}s long, but {} beats at {} bpm is {:.3}s",
+                    seconds, audio_loop.length, TEMPO, expected
+                ),
+            );
+        }
+    }
+
+    let length = audio_loop.length as f32;
+    let mut previous_beat = None;
+    for note in &audio_loop.beats {
+        if note.beat < 0. || note.beat >= length {
+            report.error(
+                name,
+                format!(
+                    "beat {} is outside the loop's {} beats",
+                    note.beat, audio_loop.length
+                ),
+            );
+        }
+        if let Some(previous_beat) = previous_beat {
+            if note.beat <= previous_beat {
+                report.error(
+                    name,
+                    format!("beat {} is listed after beat {}", note.beat, previous_beat),
+                );
+            }
+        }
+        if let Some(sustain) = note.length() {
+            if sustain <= 0. || sustain >= length {
+                report.error(
+                    name,
+                    format!(
+                        "the note on beat {} is held for {} beats",
+                        note.beat, sustain
+                    ),
+                );
+            }
+        }
+        previous_beat = Some(note.beat);
+    }
+}
+
+/// Checks that a sprite has frames to show, and has each of the tags the game
+/// plays from it.
+async fn check_sprite(report: &mut Report, name: &str, sprite: &Sprite, tags: &[&str]) {
+    if let Err(err) = sprite.get_frame(None).await {
+        report.error(name, format!("sprite has no frames: {:?}", err));
+    }
+    for tag in tags {
+        if sprite.set_current_tag(Some(*tag)).await.is_err() {
+            report.error(name, format!("sprite has no {:?} tag", tag));
+        }
+    }
+}